@@ -0,0 +1,199 @@
+use crate::rtweekend;
+use crate::vec3::Vec3;
+
+// How a keyframe's value moves towards the next one.
+#[derive(Clone, Copy)]
+pub enum Interpolation {
+    // Holds the value until the next key.
+    Step,
+    Linear,
+    // Timing curve through (0, 0), (x1, y1), (x2, y2) and (1, 1), as in CSS
+    // cubic-bezier(); the value follows the curve's progress.
+    Bezier { x1: f64, y1: f64, x2: f64, y2: f64 },
+}
+
+impl Interpolation {
+    pub const EASE_IN_OUT: Interpolation = Interpolation::Bezier {
+        x1: 0.42,
+        y1: 0.0,
+        x2: 0.58,
+        y2: 1.0,
+    };
+
+    // Progress along the segment for the fraction u of its duration.
+    fn progress(&self, u: f64) -> f64 {
+        match *self {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => u,
+            Interpolation::Bezier { x1, y1, x2, y2 } => {
+                let bezier = |a: f64, b: f64, s: f64| {
+                    3.0 * (1.0 - s) * (1.0 - s) * s * a + 3.0 * (1.0 - s) * s * s * b + s * s * s
+                };
+                // x(s) is monotonic for control points inside the unit square.
+                let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+                let (mut lo, mut hi) = (0.0, 1.0);
+                for _ in 0..32 {
+                    let mid = 0.5 * (lo + hi);
+                    if bezier(x1, x2, mid) < u {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                bezier(y1, y2, 0.5 * (lo + hi))
+            }
+        }
+    }
+}
+
+// Values that keyframes can blend between.
+pub trait Animatable: Copy {
+    fn interpolate(a: &Self, b: &Self, t: f64) -> Self;
+}
+
+impl Animatable for f64 {
+    fn interpolate(a: &Self, b: &Self, t: f64) -> Self {
+        a + t * (b - a)
+    }
+}
+
+impl Animatable for Vec3 {
+    fn interpolate(a: &Self, b: &Self, t: f64) -> Self {
+        (1.0 - t) * *a + t * *b
+    }
+}
+
+// Unit quaternion w + xi + yj + zk representing a rotation.
+#[derive(Clone, Copy)]
+pub struct Quat {
+    w: f64,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+impl Quat {
+    pub fn identity() -> Self {
+        Quat { w: 1.0, x: 0.0, y: 0.0, z: 0.0 }
+    }
+
+    // Rotation by degrees counterclockwise about axis.
+    pub fn from_axis_angle(axis: &Vec3, degrees: f64) -> Self {
+        let half = 0.5 * rtweekend::degrees_to_radians(degrees);
+        let a = half.sin() * Vec3::unit_vector(axis);
+        Quat { w: half.cos(), x: a.x(), y: a.y(), z: a.z() }
+    }
+
+    // Rotation taking the x, y and z axes to the orthonormal basis u, v, w.
+    pub fn from_basis(u: &Vec3, v: &Vec3, w: &Vec3) -> Self {
+        let trace = u.x() + v.y() + w.z();
+        let q = if trace > 0.0 {
+            let s = 0.5 / (trace + 1.0).sqrt();
+            Quat { w: 0.25 / s, x: (v.z() - w.y()) * s, y: (w.x() - u.z()) * s, z: (u.y() - v.x()) * s }
+        } else if u.x() > v.y() && u.x() > w.z() {
+            let s = 2.0 * (1.0 + u.x() - v.y() - w.z()).sqrt();
+            Quat { w: (v.z() - w.y()) / s, x: 0.25 * s, y: (v.x() + u.y()) / s, z: (w.x() + u.z()) / s }
+        } else if v.y() > w.z() {
+            let s = 2.0 * (1.0 + v.y() - u.x() - w.z()).sqrt();
+            Quat { w: (w.x() - u.z()) / s, x: (v.x() + u.y()) / s, y: 0.25 * s, z: (w.y() + v.z()) / s }
+        } else {
+            let s = 2.0 * (1.0 + w.z() - u.x() - v.y()).sqrt();
+            Quat { w: (u.y() - v.x()) / s, x: (w.x() + u.z()) / s, y: (w.y() + v.z()) / s, z: 0.25 * s }
+        };
+        q.normalized()
+    }
+
+    fn dot(&self, other: &Quat) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn normalized(&self) -> Self {
+        let len = self.dot(self).sqrt();
+        Quat { w: self.w / len, x: self.x / len, y: self.y / len, z: self.z / len }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Quat { w: self.w, x: -self.x, y: -self.y, z: -self.z }
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * Vec3::cross(&q, v);
+        *v + self.w * t + Vec3::cross(&q, &t)
+    }
+}
+
+// Spherical linear interpolation along the shorter arc.
+impl Animatable for Quat {
+    fn interpolate(a: &Self, b: &Self, t: f64) -> Self {
+        let mut cos_theta = a.dot(b);
+        let b = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Quat { w: -b.w, x: -b.x, y: -b.y, z: -b.z }
+        } else {
+            *b
+        };
+        let (wa, wb) = if cos_theta > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            (((1.0 - t) * theta).sin() / theta.sin(), (t * theta).sin() / theta.sin())
+        };
+        Quat {
+            w: wa * a.w + wb * b.w,
+            x: wa * a.x + wb * b.x,
+            y: wa * a.y + wb * b.y,
+            z: wa * a.z + wb * b.z,
+        }
+        .normalized()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    // Interpolation towards the next keyframe.
+    pub interpolation: Interpolation,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f64, value: T, interpolation: Interpolation) -> Self {
+        Keyframe { time, value, interpolation }
+    }
+}
+
+// Value changing over time through keyframes, held constant before the first
+// and after the last.
+#[derive(Clone)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    // Panics on an empty key list, which has no value to hold.
+    pub fn new(mut keys: Vec<Keyframe<T>>) -> Self {
+        assert!(!keys.is_empty(), "an animation track needs at least one keyframe");
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Track { keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Track {
+            keys: vec![Keyframe::new(0.0, value, Interpolation::Step)],
+        }
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let next = self.keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keys[0].value;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].value;
+        }
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let u = (time - a.time) / (b.time - a.time);
+        T::interpolate(&a.value, &b.value, a.interpolation.progress(u))
+    }
+}
@@ -0,0 +1,114 @@
+use std::rc::Rc;
+
+use crate::rtweekend::{random, PI};
+use crate::texture::Texture;
+use crate::vec3::{Point3, Vec3};
+
+const MASK_RESOLUTION: usize = 64;
+
+// Shape of the lens opening in units of the lens radius; cameras scale the
+// samples by their lens radius.
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    // Regular polygon inscribed in the unit circle, rotated by degrees.
+    Polygon { blades: u32, rotation: f64 },
+    // Transmission over [-1, 1]^2 tabulated from a texture, row by row.
+    Mask { cells: Vec<f64>, cdf: Vec<f64> },
+}
+
+impl Aperture {
+    pub fn polygon(blades: u32, rotation: f64) -> Self {
+        Aperture::Polygon {
+            blades: blades.max(3),
+            rotation,
+        }
+    }
+
+    // The texture is read over its unit square (u, v), scaled to the opening.
+    pub fn mask(texture: Rc<dyn Texture>) -> Self {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut cells = Vec::with_capacity(MASK_RESOLUTION * MASK_RESOLUTION);
+        let mut cdf = Vec::with_capacity(MASK_RESOLUTION * MASK_RESOLUTION);
+        let mut sum = 0.0;
+        for j in 0..MASK_RESOLUTION {
+            for i in 0..MASK_RESOLUTION {
+                let u = (i as f64 + 0.5) / MASK_RESOLUTION as f64;
+                let v = (j as f64 + 0.5) / MASK_RESOLUTION as f64;
+                let value = texture.scalar(u, v, &origin, 0.0).clamp(0.0, 1.0);
+                sum += value;
+                cells.push(value);
+                cdf.push(sum);
+            }
+        }
+        Aperture::Mask { cells, cdf }
+    }
+
+    // Point on the opening, or None if nothing gets through it.
+    pub fn sample(&self) -> Option<(f64, f64)> {
+        match self {
+            Aperture::Circle => {
+                let p = Vec3::random_in_unit_disk();
+                Some((p.x(), p.y()))
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // Uniform over one of the equal triangles fanning out from the center.
+                let sector = 2.0 * PI / *blades as f64;
+                let k = (random() * *blades as f64).floor();
+                let a0 = rotation.to_radians() + k * sector;
+                let a1 = a0 + sector;
+                let su = random().sqrt();
+                let r2 = random();
+                let x = su * ((1.0 - r2) * a0.cos() + r2 * a1.cos());
+                let y = su * ((1.0 - r2) * a0.sin() + r2 * a1.sin());
+                Some((x, y))
+            }
+            Aperture::Mask { cdf, .. } => {
+                let total = *cdf.last()?;
+                if total <= 0.0 {
+                    return None;
+                }
+                let target = random() * total;
+                let index = cdf.partition_point(|&c| c <= target).min(cdf.len() - 1);
+                let cell = 2.0 / MASK_RESOLUTION as f64;
+                let x = -1.0 + ((index % MASK_RESOLUTION) as f64 + random()) * cell;
+                let y = -1.0 + ((index / MASK_RESOLUTION) as f64 + random()) * cell;
+                Some((x, y))
+            }
+        }
+    }
+
+    // Density of sample producing (x, y), per unit area of the opening.
+    pub fn pdf(&self, x: f64, y: f64) -> f64 {
+        match self {
+            Aperture::Circle => {
+                if x * x + y * y < 1.0 {
+                    1.0 / PI
+                } else {
+                    0.0
+                }
+            }
+            Aperture::Polygon { blades, rotation } => {
+                let n = *blades as f64;
+                let sector = 2.0 * PI / n;
+                let phi = (y.atan2(x) - rotation.to_radians()).rem_euclid(2.0 * PI);
+                let middle = rotation.to_radians() + ((phi / sector).floor() + 0.5) * sector;
+                if x * middle.cos() + y * middle.sin() > (sector / 2.0).cos() + 1e-9 {
+                    return 0.0;
+                }
+                1.0 / (0.5 * n * sector.sin())
+            }
+            Aperture::Mask { cells, cdf } => {
+                let total = cdf.last().copied().unwrap_or(0.0);
+                if total <= 0.0 || x.abs() >= 1.0 || y.abs() >= 1.0 {
+                    return 0.0;
+                }
+                let i = ((x + 1.0) / 2.0 * MASK_RESOLUTION as f64) as usize;
+                let j = ((y + 1.0) / 2.0 * MASK_RESOLUTION as f64) as usize;
+                let cell = 2.0 / MASK_RESOLUTION as f64;
+                cells[j.min(MASK_RESOLUTION - 1) * MASK_RESOLUTION + i.min(MASK_RESOLUTION - 1)]
+                    / (total * cell * cell)
+            }
+        }
+    }
+}
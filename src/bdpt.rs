@@ -0,0 +1,500 @@
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::hittable::{HitRecord, Hittable};
+use crate::integrator::Integrator;
+use crate::ray::Ray;
+use crate::rtweekend::PI;
+use crate::scene::Scene;
+use crate::vec3::{Color, Point3, Vec3};
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+#[derive(Clone)]
+struct Vertex {
+    kind: VertexKind,
+    p: Point3,
+    n: Vec3,
+    // Direction towards the previous vertex of the subpath.
+    wo: Vec3,
+    rec: Option<HitRecord>,
+    // Time of the path, which places moving cameras.
+    time: f64,
+    beta: Color,
+    delta: bool,
+    // Area densities of sampling this vertex from its predecessor (fwd) and
+    // from its successor (rev).
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl Vertex {
+    fn camera(r: &Ray, cam: &dyn Camera, beta: Color) -> Self {
+        Vertex {
+            kind: VertexKind::Camera,
+            p: r.origin(),
+            n: Vec3::unit_vector(&r.direction()),
+            wo: Vec3::new(0.0, 0.0, 0.0),
+            rec: None,
+            time: r.time(),
+            beta,
+            // Without an importance function light paths cannot be connected to
+            // the lens, which MIS treats like a delta vertex.
+            delta: !cam.has_importance(),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn light(rec: HitRecord, beta: Color, pdf: f64) -> Self {
+        Vertex {
+            kind: VertexKind::Light,
+            p: rec.p,
+            n: rec.normal,
+            wo: Vec3::new(0.0, 0.0, 0.0),
+            time: rec.time,
+            rec: Some(rec),
+            beta,
+            delta: false,
+            pdf_fwd: pdf,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(rec: HitRecord, wo: Vec3, beta: Color, pdf_dir: f64, prev: &Vertex) -> Self {
+        let mut v = Vertex {
+            kind: VertexKind::Surface,
+            p: rec.p,
+            n: rec.normal,
+            wo,
+            time: rec.time,
+            rec: Some(rec),
+            beta,
+            delta: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        };
+        v.pdf_fwd = prev.convert_density(pdf_dir, &v);
+        v
+    }
+
+    fn rec(&self) -> &HitRecord {
+        self.rec.as_ref().expect("camera vertices have no hit record")
+    }
+
+    fn on_surface(&self) -> bool {
+        self.kind != VertexKind::Camera
+    }
+
+    fn is_connectible(&self) -> bool {
+        match self.kind {
+            VertexKind::Surface => !self.rec().mat.is_specular(),
+            _ => true,
+        }
+    }
+
+    fn f(&self, next: &Vertex) -> Color {
+        let wi = Vec3::unit_vector(&(next.p - self.p));
+        let rec = self.rec();
+        rec.mat.eval(rec, &self.wo, &wi)
+    }
+
+    // Radiance emitted from this vertex towards v.
+    fn le(&self, v: &Vertex) -> Color {
+        if Vec3::dot(&(v.p - self.p), &self.n) <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let rec = self.rec();
+        rec.mat.emitted(rec)
+    }
+
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let w = next.p - self.p;
+        let dist_squared = w.length_squared();
+        if dist_squared == 0.0 {
+            return 0.0;
+        }
+        let pdf = pdf / dist_squared;
+        if next.on_surface() {
+            pdf * Vec3::dot(&next.n, &(w / dist_squared.sqrt())).abs()
+        } else {
+            pdf
+        }
+    }
+
+    // Area density of sampling next when this vertex was reached from prev.
+    fn pdf(&self, cam: &dyn Camera, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        let wn = Vec3::unit_vector(&(next.p - self.p));
+        let pdf = match self.kind {
+            VertexKind::Light => return self.pdf_light(next),
+            VertexKind::Camera => cam.pdf_we(&Ray::new(&self.p, &wn, self.time)).1,
+            VertexKind::Surface => {
+                let wp = Vec3::unit_vector(&(prev.expect("surface vertex without predecessor").p - self.p));
+                let rec = self.rec();
+                rec.mat.pdf(rec, &wp, &wn)
+            }
+        };
+        self.convert_density(pdf, next)
+    }
+
+    // Area density of a light at this vertex emitting towards v.
+    fn pdf_light(&self, v: &Vertex) -> f64 {
+        let w = v.p - self.p;
+        let dist_squared = w.length_squared();
+        let w = w / dist_squared.sqrt();
+        let pdf = Vec3::dot(&self.n, &w).max(0.0) / PI / dist_squared;
+        if v.on_surface() {
+            pdf * Vec3::dot(&v.n, &w).abs()
+        } else {
+            pdf
+        }
+    }
+
+    fn pdf_light_origin(&self, scene: &Scene, time: f64) -> f64 {
+        scene.lights.surface_pdf(&self.p, time)
+    }
+}
+
+// Bidirectional path tracer: every camera sample also traces a subpath from a
+// light, and all ways of joining the two subpaths are combined with the
+// balance heuristic. Connections to the lens are splatted onto the film.
+pub struct Bdpt {
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+}
+
+impl Bdpt {
+    fn random_walk(
+        scene: &Scene,
+        mut r: Ray,
+        mut beta: Color,
+        pdf_dir: f64,
+        max_vertices: usize,
+        path: &mut Vec<Vertex>,
+    ) -> Option<(Ray, Color)> {
+        let mut pdf_fwd = pdf_dir;
+        // Scattering inside a medium adds no vertex; connections running
+        // through a medium are attenuated by its transmittance instead.
+        let mut medium_events = 0;
+        while path.len() + medium_events < max_vertices {
+            let rec = match scene.world.hit(&r, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => return Some((r, beta)),
+            };
+            let (transmittance, scattered) = rec.mat.sample_interior(&r, &rec);
+            beta = beta * transmittance;
+            if let Some(scattered) = scattered {
+                r = scattered;
+                medium_events += 1;
+                continue;
+            }
+            let wo = -Vec3::unit_vector(&r.direction());
+            let vertex = Vertex::surface(rec.clone(), wo, beta, pdf_fwd, &path[path.len() - 1]);
+            path.push(vertex);
+            if path.len() >= max_vertices {
+                break;
+            }
+
+            let (scattered, attenuation) = match rec.mat.scatter(&r, &rec) {
+                Some(s) => s,
+                None => break,
+            };
+            let wi = Vec3::unit_vector(&scattered.direction());
+            let cur = path.len() - 1;
+            let pdf_rev = if rec.mat.is_specular() {
+                path[cur].delta = true;
+                pdf_fwd = 0.0;
+                0.0
+            } else {
+                pdf_fwd = rec.mat.pdf(&rec, &wo, &wi);
+                rec.mat.pdf(&rec, &wi, &wo)
+            };
+            beta = beta * attenuation;
+            path[cur - 1].pdf_rev = path[cur].convert_density(pdf_rev, &path[cur - 1]);
+            r = scattered;
+        }
+        None
+    }
+
+    // Throughput of the segment from p0 to p1: zero when something lies in
+    // between, and the attenuation of the medium when the segment runs inside
+    // an object, which shows as reaching p1's surface from behind. Media that
+    // scatter give an unbiased estimate, counting paths that scatter as lost.
+    fn transmittance(scene: &Scene, p0: &Point3, p1: &Point3, time: f64) -> Color {
+        let d = *p1 - *p0;
+        let dist = d.length();
+        let r = Ray::new(p0, &(d / dist), time);
+        match scene.world.hit(&r, 0.001, dist + 0.001) {
+            None => Color::new(1.0, 1.0, 1.0),
+            Some(rec) if rec.t < dist - 0.001 => Color::new(0.0, 0.0, 0.0),
+            Some(rec) => match rec.mat.sample_interior(&r, &rec) {
+                (tr, None) => tr,
+                (_, Some(_)) => Color::new(0.0, 0.0, 0.0),
+            },
+        }
+    }
+
+    fn geometry(scene: &Scene, v0: &Vertex, v1: &Vertex, time: f64) -> Color {
+        let d = v0.p - v1.p;
+        let dist_squared = d.length_squared();
+        let d = d / dist_squared.sqrt();
+        let mut g = 1.0 / dist_squared;
+        if v0.on_surface() {
+            g *= Vec3::dot(&v0.n, &d).abs();
+        }
+        if v1.on_surface() {
+            g *= Vec3::dot(&v1.n, &d).abs();
+        }
+        if g > 0.0 {
+            g * Self::transmittance(scene, &v0.p, &v1.p, time)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn mis_weight(
+        scene: &Scene,
+        cam: &dyn Camera,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<Vertex>,
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        let mut light_path = light_path[..s].to_vec();
+        let mut camera_path = camera_path[..t].to_vec();
+        if let Some(v) = sampled {
+            if s == 1 {
+                light_path[0] = v;
+            } else {
+                camera_path[0] = v;
+            }
+        }
+
+        // Densities of the connection endpoints being sampled from the other side.
+        let pt = &camera_path[t - 1];
+        let qs = if s > 0 { Some(&light_path[s - 1]) } else { None };
+        let pt_rev = match qs {
+            Some(qs) => qs.pdf(cam, if s > 1 { Some(&light_path[s - 2]) } else { None }, pt),
+            None => pt.pdf_light_origin(scene, time),
+        };
+        let pt_minus_rev = if t > 1 {
+            match qs {
+                Some(qs) => pt.pdf(cam, Some(qs), &camera_path[t - 2]),
+                None => pt.pdf_light(&camera_path[t - 2]),
+            }
+        } else {
+            0.0
+        };
+        let qs_rev = qs.map(|qs| pt.pdf(cam, if t > 1 { Some(&camera_path[t - 2]) } else { None }, qs));
+        let qs_minus_rev = if s > 1 {
+            qs.map(|qs| qs.pdf(cam, Some(pt), &light_path[s - 2]))
+        } else {
+            None
+        };
+
+        camera_path[t - 1].pdf_rev = pt_rev;
+        camera_path[t - 1].delta = false;
+        if t > 1 {
+            camera_path[t - 2].pdf_rev = pt_minus_rev;
+        }
+        if let Some(pdf) = qs_rev {
+            light_path[s - 1].pdf_rev = pdf;
+            light_path[s - 1].delta = false;
+        }
+        if let Some(pdf) = qs_minus_rev {
+            light_path[s - 2].pdf_rev = pdf;
+        }
+
+        let remap0 = |f: f64| if f != 0.0 { f } else { 1.0 };
+        let mut sum_ri = 0.0;
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap0(camera_path[i].pdf_rev) / remap0(camera_path[i].pdf_fwd);
+            if !camera_path[i].delta && !camera_path[i - 1].delta {
+                sum_ri += ri;
+            }
+        }
+        ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap0(light_path[i].pdf_rev) / remap0(light_path[i].pdf_fwd);
+            let delta_light_vertex = i > 0 && light_path[i - 1].delta;
+            if !light_path[i].delta && !delta_light_vertex {
+                sum_ri += ri;
+            }
+        }
+        1.0 / (1.0 + sum_ri)
+    }
+
+    // Joins the first s light vertices with the first t camera vertices.
+    // Returns the weighted contribution and, for t == 1, where to splat it.
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        scene: &Scene,
+        cam: &dyn Camera,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> Option<(Color, Option<(f64, f64)>)> {
+        let pt = &camera_path[t - 1];
+        let mut sampled = None;
+        let mut raster = None;
+
+        let l = if s == 0 {
+            if pt.kind != VertexKind::Surface {
+                return None;
+            }
+            pt.beta * pt.le(&camera_path[t - 2])
+        } else if t == 1 {
+            let qs = &light_path[s - 1];
+            if !cam.has_importance() || !qs.is_connectible() {
+                return None;
+            }
+            let (r, pdf, we, u, v) = cam.sample_wi(&qs.p, time)?;
+            if pdf <= 0.0 {
+                return None;
+            }
+            let v_cam = Vertex::camera(&r, cam, we / pdf);
+            let cos_q = Vec3::dot(&r.direction(), &qs.n).abs();
+            let l = qs.beta * qs.f(&v_cam) * v_cam.beta * cos_q;
+            if l.near_zero() {
+                return None;
+            }
+            let l = l * Self::transmittance(scene, &qs.p, &v_cam.p, time);
+            sampled = Some(v_cam);
+            raster = Some((u, v));
+            l
+        } else if s == 1 {
+            if !pt.is_connectible() {
+                return None;
+            }
+            let (rec, pdf_pos) = scene.lights.sample_surface(time)?;
+            let d = rec.p - pt.p;
+            let dist_squared = d.length_squared();
+            let d = d / dist_squared.sqrt();
+            let cos_light = -Vec3::dot(&rec.normal, &d);
+            if cos_light <= 0.0 {
+                return None;
+            }
+            let le = rec.mat.emitted(&rec);
+            let v_light = Vertex::light(rec, le * cos_light / (pdf_pos * dist_squared), pdf_pos);
+            let l = pt.beta * pt.f(&v_light) * v_light.beta * Vec3::dot(&d, &pt.n).abs();
+            if l.near_zero() {
+                return None;
+            }
+            let l = l * Self::transmittance(scene, &pt.p, &v_light.p, time);
+            sampled = Some(v_light);
+            l
+        } else {
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() || !pt.is_connectible() {
+                return None;
+            }
+            let l = qs.beta * qs.f(pt) * pt.f(qs) * pt.beta;
+            if l.near_zero() {
+                return None;
+            }
+            l * Self::geometry(scene, qs, pt, time)
+        };
+
+        if l.near_zero() {
+            return None;
+        }
+        let weight = Self::mis_weight(scene, cam, light_path, camera_path, sampled, s, t, time);
+        Some((weight * l, raster))
+    }
+
+    fn generate_light_subpath(&self, scene: &Scene, time: f64) -> Vec<Vertex> {
+        let mut path = Vec::new();
+        let (rec, pdf_pos) = match scene.lights.sample_surface(time) {
+            Some(sample) => sample,
+            None => return path,
+        };
+        let le = rec.mat.emitted(&rec);
+        let n = rec.normal;
+        let dir = n + Vec3::random_unit_vector();
+        let dir = if dir.near_zero() { n } else { Vec3::unit_vector(&dir) };
+        let pdf_dir = Vec3::dot(&n, &dir) / PI;
+        if pdf_dir <= 0.0 || le.near_zero() {
+            return path;
+        }
+
+        let r = Ray::new(&rec.p, &dir, time);
+        let beta = le * Vec3::dot(&n, &dir) / (pdf_pos * pdf_dir);
+        path.push(Vertex::light(rec, le / pdf_pos, pdf_pos));
+        Self::random_walk(scene, r, beta, pdf_dir, self.max_depth as usize + 1, &mut path);
+        path
+    }
+
+    fn trace(&self, scene: &Scene, cam: &dyn Camera, film: &mut Film, r: &Ray) -> Color {
+        let max_depth = self.max_depth as usize;
+        let time = r.time();
+        let mut l = Color::new(0.0, 0.0, 0.0);
+
+        let mut camera_path = vec![Vertex::camera(r, cam, Color::new(1.0, 1.0, 1.0))];
+        let (_, pdf_dir) = cam.pdf_we(r);
+        let escaped = Self::random_walk(
+            scene,
+            *r,
+            Color::new(1.0, 1.0, 1.0),
+            pdf_dir,
+            max_depth + 2,
+            &mut camera_path,
+        );
+        // Only the camera subpath can reach the background, so no weighting is needed.
+        if let Some((escaped, beta)) = escaped {
+            l += beta * scene.background(&escaped);
+        }
+
+        let light_path = self.generate_light_subpath(scene, time);
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                if s + t < 2 || (s == 1 && t == 1) || s + t - 2 > max_depth {
+                    continue;
+                }
+                if let Some((c, raster)) = Self::connect(scene, cam, &light_path, &camera_path, s, t, time) {
+                    match raster {
+                        Some((u, v)) => film.add_splat(u, v, c),
+                        None => l += c,
+                    }
+                }
+            }
+        }
+        l
+    }
+}
+
+impl Integrator for Bdpt {
+    fn render(&self, scene: &Scene, cam: &dyn Camera, film: &mut Film) {
+        for j in (0..film.height).rev() {
+            eprint!("\rScanlines remaining: {} ", j);
+            for i in 0..film.width {
+                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+                for _ in 0..self.samples_per_pixel {
+                    // The same mapping as PathTracer, which add_splat inverts so
+                    // that splatted light paths land on the same pixels.
+                    let (u, v) = film.sample_raster(i, j);
+
+                    if let Some(r) = cam.get_ray(u, v) {
+                        pixel_color += self.trace(scene, cam, film, &r);
+                    }
+                }
+                film.add_sample(i, j, pixel_color);
+            }
+        }
+        eprintln!("\nDone.");
+    }
+}
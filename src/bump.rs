@@ -0,0 +1,96 @@
+use std::rc::Rc;
+
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+
+// Step in (u, v) for the finite differences of bump maps.
+const BUMP_DELTA: f64 = 0.0005;
+
+pub enum Perturbation {
+    // Tangent space normals encoded as colours in [0, 1], with x along dpdu,
+    // y across it and z along the surface normal.
+    NormalMap(Rc<dyn Texture>),
+    // Height field, scaled by scale, displacing the surface along its normal.
+    Bump { height: Rc<dyn Texture>, scale: f64 },
+}
+
+// Wraps a material so that it shades with a normal perturbed by a normal or
+// bump map. Only the shading normal changes; the geometry stays smooth.
+pub struct Bumped {
+    base: Rc<dyn Material>,
+    perturbation: Perturbation,
+}
+
+impl Bumped {
+    pub fn new(base: Rc<dyn Material>, perturbation: Perturbation) -> Self {
+        Bumped { base, perturbation }
+    }
+
+    fn shade(&self, rec: &HitRecord) -> HitRecord {
+        let n = rec.normal;
+        let shading_normal = match &self.perturbation {
+            Perturbation::NormalMap(map) => {
+                let c = map.value(rec.u, rec.v, &rec.p, rec.time);
+                let t = rec.dpdu - Vec3::dot(&n, &rec.dpdu) * n;
+                let t = if t.near_zero() {
+                    Onb::from_w(&n).u
+                } else {
+                    Vec3::unit_vector(&t)
+                };
+                let b = Vec3::cross(&n, &t);
+                (2.0 * c.x() - 1.0) * t + (2.0 * c.y() - 1.0) * b + (2.0 * c.z() - 1.0) * n
+            }
+            Perturbation::Bump { height, scale } => {
+                let h = |du: f64, dv: f64| {
+                    let p = rec.p + du * rec.dpdu + dv * rec.dpdv;
+                    scale * height.scalar(rec.u + du, rec.v + dv, &p, rec.time)
+                };
+                let h0 = h(0.0, 0.0);
+                let dpdu = rec.dpdu + (h(BUMP_DELTA, 0.0) - h0) / BUMP_DELTA * n;
+                let dpdv = rec.dpdv + (h(0.0, BUMP_DELTA) - h0) / BUMP_DELTA * n;
+                let m = Vec3::cross(&dpdu, &dpdv);
+                if Vec3::dot(&m, &n) < 0.0 {
+                    -m
+                } else {
+                    m
+                }
+            }
+        };
+
+        let mut shaded = rec.clone();
+        if !shading_normal.near_zero() && Vec3::dot(&shading_normal, &n) > 0.0 {
+            shaded.normal = Vec3::unit_vector(&shading_normal);
+        }
+        shaded
+    }
+}
+
+impl Material for Bumped {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        self.base.scatter(r_in, &self.shade(rec))
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        self.base.eval(&self.shade(rec), wo, wi)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> f64 {
+        self.base.pdf(&self.shade(rec), wo, wi)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.base.is_specular()
+    }
+
+    fn sample_interior(&self, r: &Ray, rec: &HitRecord) -> (Color, Option<Ray>) {
+        self.base.sample_interior(r, rec)
+    }
+}
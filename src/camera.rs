@@ -1,9 +1,124 @@
+use crate::animation::{Quat, Track};
+use crate::aperture::Aperture;
 use crate::rtweekend;
 
 use super::ray::Ray;
-use super::vec3::{Point3, Vec3};
+use super::vec3::{Color, Point3, Vec3};
 
-pub struct Camera {
+// How far open the shutter is over its interval.
+#[derive(Clone, Copy)]
+pub enum ShutterProfile {
+    // Fully open from open to close.
+    Box,
+    // Opens linearly up to the middle of the interval and closes again.
+    Triangle,
+    // Opens and closes along smoothstep ramps, each taking the given fraction
+    // of the interval.
+    Smooth { ramp: f64 },
+}
+
+// Interval during which the shutter is open; every camera draws ray times from it.
+#[derive(Clone, Copy)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+    pub profile: ShutterProfile,
+    // Rolling shutter: time the exposure takes to sweep from the top row of
+    // the image to the bottom row. Each row is exposed over the same profile,
+    // delayed by its distance from the top.
+    pub readout: f64,
+}
+
+impl Shutter {
+    pub fn new(open: f64, close: f64) -> Self {
+        Shutter {
+            open,
+            close,
+            profile: ShutterProfile::Box,
+            readout: 0.0,
+        }
+    }
+
+    pub fn with_profile(mut self, profile: ShutterProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_rolling(mut self, readout: f64) -> Self {
+        self.readout = readout;
+        self
+    }
+
+    // Exposure of frame number frame (counting from 1) of a sequence at fps
+    // frames per second, open for shutter_angle degrees of each 360 degree
+    // frame interval. The profile and readout are kept.
+    pub fn for_frame(mut self, frame: i32, fps: f64, shutter_angle: f64) -> Self {
+        self.open = (frame - 1) as f64 / fps;
+        self.close = self.open + shutter_angle.clamp(0.0, 360.0) / 360.0 / fps;
+        self
+    }
+
+    // Time for a ray through image row t, with t = 1 at the top.
+    pub fn sample(&self, t: f64) -> f64 {
+        let offset = (1.0 - t.clamp(0.0, 1.0)) * self.readout;
+        let u = match self.profile {
+            ShutterProfile::Box => rtweekend::random(),
+            ShutterProfile::Triangle => 0.5 * (rtweekend::random() + rtweekend::random()),
+            ShutterProfile::Smooth { ramp } => {
+                let ramp = ramp.clamp(1e-6, 0.5);
+                let smoothstep = |x: f64| x * x * (3.0 - 2.0 * x);
+                // The profile is at most 1, so rejection takes under two tries on average.
+                loop {
+                    let u = rtweekend::random();
+                    let openness = smoothstep((u / ramp).min(1.0)) * smoothstep(((1.0 - u) / ramp).min(1.0));
+                    if rtweekend::random() < openness {
+                        break u;
+                    }
+                }
+            }
+        };
+        self.open + offset + u * (self.close - self.open)
+    }
+}
+
+pub trait Camera {
+    // Ray through image coordinates (s, t) in [0, 1]^2, (0, 0) being the bottom
+    // left corner, or None where the projection sees nothing.
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray>;
+
+    fn shutter(&self) -> &Shutter;
+
+    // Ray through image point (s, t) from the center of the lens, as a pinhole
+    // there would see it; used to pick what to focus on.
+    fn central_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.get_ray(s, t)
+    }
+
+    // Time for a ray through image row t.
+    fn sample_time(&self, t: f64) -> f64 {
+        self.shutter().sample(t)
+    }
+
+    // Light tracing support. Cameras without an importance function can only be
+    // reached by paths started from the camera.
+    fn has_importance(&self) -> bool {
+        false
+    }
+
+    fn we(&self, _r: &Ray) -> Option<(Color, f64, f64)> {
+        None
+    }
+
+    fn pdf_we(&self, _r: &Ray) -> (f64, f64) {
+        (0.0, 0.0)
+    }
+
+    fn sample_wi(&self, _p: &Point3, _time: f64) -> Option<(Ray, f64, Color, f64, f64)> {
+        None
+    }
+}
+
+pub struct PerspectiveCamera {
     origin: Point3,
     lower_left_corner: Point3,
     horizontal: Vec3,
@@ -12,11 +127,17 @@ pub struct Camera {
     v: Vec3,
     w: Vec3,
     lens_radius: f64,
-    time0: f64,         // shutter open time
-    time1: f64,         // shutter close time
+    aperture: Aperture,
+    // Shift of the clipping pupil, in lens radii per half image height off center.
+    cat_eye: f64,
+    focus_dist: f64,
+    // Normal of the plane of focus, which passes through the axis at focus_dist.
+    focus_normal: Vec3,
+    shutter: Shutter,
 }
 
-impl Camera {
+impl PerspectiveCamera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
@@ -25,8 +146,7 @@ impl Camera {
         aspect_ratio: f64,
         aperture: f64,
         focus_dist: f64,
-        time0: f64,
-        time1: f64
+        shutter: Shutter
     ) -> Self {
         let theta = rtweekend::degrees_to_radians(vfov);
         let h = (theta / 2.0).tan();
@@ -44,7 +164,7 @@ impl Camera {
 
         let lens_radius = aperture / 2.0;
 
-        Camera {
+        PerspectiveCamera {
             origin,
             lower_left_corner,
             horizontal,
@@ -53,20 +173,284 @@ impl Camera {
             u,
             v,
             lens_radius,
-            time0,
-            time1,
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
+            focus_dist,
+            focus_normal: w,
+            shutter,
+        }
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    // Cat's-eye vignetting: off-axis, a second pupil of the same radius shifted
+    // towards the image edge clips the aperture, so bokeh near the frame edges
+    // turns into lens shapes and the corners darken.
+    pub fn with_cat_eye(mut self, cat_eye: f64) -> Self {
+        self.cat_eye = cat_eye;
+        self
+    }
+
+    // Slides the image window within the image plane, in units of its width and
+    // height, without tilting the view.
+    pub fn with_shift(mut self, shift_x: f64, shift_y: f64) -> Self {
+        self.lower_left_corner += shift_x * self.horizontal + shift_y * self.vertical;
+        self
+    }
+
+    // Scheimpflug: turns the plane of focus, in degrees, about the horizontal
+    // (tilt) and vertical (swing) image axes, so that the focus distance varies
+    // across the frame. Positive tilt leans the plane back so that it recedes
+    // towards the top of the frame, like the ground; positive swing makes it
+    // recede towards the right.
+    pub fn with_tilt(mut self, tilt: f64, swing: f64) -> Self {
+        let (tilt, swing) = (rtweekend::degrees_to_radians(tilt), rtweekend::degrees_to_radians(swing));
+        self.focus_normal = tilt.cos() * (swing.cos() * self.w + swing.sin() * self.u) + tilt.sin() * self.v;
+        self
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        -self.w
+    }
+
+    // Where a ray from p along d meets the plane of focus, or None when the
+    // plane is not ahead of it and the ray is focused at infinity.
+    fn focus_point(&self, p: &Point3, d: &Vec3) -> Option<Point3> {
+        let plane_point = self.origin - self.focus_dist * self.w;
+        let t = Vec3::dot(&(plane_point - *p), &self.focus_normal) / Vec3::dot(d, &self.focus_normal);
+        if t.is_finite() && t > 0.0 {
+            Some(*p + t * *d)
+        } else {
+            None
+        }
+    }
+
+    // Area of the image plane at unit distance from the lens.
+    fn image_area(&self) -> f64 {
+        self.horizontal.length() * self.vertical.length() / (self.focus_dist * self.focus_dist)
+    }
+
+    // Aperture coordinates of a point on the lens.
+    fn lens_local(&self, p: &Point3) -> (f64, f64) {
+        let d = *p - self.origin;
+        (
+            Vec3::dot(&d, &self.u) / self.lens_radius,
+            Vec3::dot(&d, &self.v) / self.lens_radius,
+        )
+    }
+
+    // Area density of the lens sample at p. A pinhole only sees through its
+    // center, so rays from anywhere else, such as another eye, carry nothing.
+    fn lens_pdf(&self, p: &Point3) -> f64 {
+        if self.lens_radius > 0.0 {
+            let (x, y) = self.lens_local(p);
+            self.aperture.pdf(x, y) / (self.lens_radius * self.lens_radius)
+        } else if (*p - self.origin).length_squared() < 1.0e-12 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    // Whether the lens point (x, y) is outside the clipping pupil seen from
+    // image coordinates (s, t).
+    fn vignetted(&self, s: f64, t: f64, x: f64, y: f64) -> bool {
+        if self.cat_eye == 0.0 || self.lens_radius <= 0.0 {
+            return false;
+        }
+        let aspect_ratio = self.horizontal.length() / self.vertical.length();
+        let cx = self.cat_eye * (2.0 * s - 1.0) * aspect_ratio;
+        let cy = self.cat_eye * (2.0 * t - 1.0);
+        (x - cx) * (x - cx) + (y - cy) * (y - cy) > 1.0
+    }
+
+    fn sample_lens(&self) -> Option<(f64, f64)> {
+        if self.lens_radius > 0.0 {
+            self.aperture.sample()
+        } else {
+            Some((0.0, 0.0))
+        }
+    }
+
+    // Maps a ray leaving the lens back to its (s, t) image coordinates, along
+    // with the solid angle density of get_ray producing its direction.
+    fn raster(&self, r: &Ray) -> Option<(f64, f64, f64)> {
+        let dir = Vec3::unit_vector(&r.direction());
+        let cos_theta = Vec3::dot(&dir, &self.forward());
+        if cos_theta <= 0.0 {
+            return None;
+        }
+        // The image point is where the ray's focus point is seen through the
+        // lens center. Its density converts from the image to the plane of
+        // focus as seen from the lens center, and from there to directions at
+        // the ray origin.
+        let (d, pdf_dir) = match self.focus_point(&r.origin(), &dir) {
+            Some(p_focus) => {
+                let d = p_focus - self.origin;
+                let cos_center = Vec3::dot(&Vec3::unit_vector(&d), &self.forward());
+                if cos_center <= 0.0 {
+                    return None;
+                }
+                let cos_plane_center = Vec3::dot(&Vec3::unit_vector(&d), &self.focus_normal).abs();
+                let cos_plane = Vec3::dot(&dir, &self.focus_normal).abs();
+                let pdf = cos_plane_center * (p_focus - r.origin()).length_squared()
+                    / (self.image_area() * cos_center.powi(3) * d.length_squared() * cos_plane);
+                (d, pdf)
+            }
+            None => (dir, 1.0 / (self.image_area() * cos_theta.powi(3))),
+        };
+        let p_image = self.origin + (self.focus_dist / Vec3::dot(&d, &self.forward())) * d;
+        let d = p_image - self.lower_left_corner;
+        let s = Vec3::dot(&d, &self.horizontal) / self.horizontal.length_squared();
+        let t = Vec3::dot(&d, &self.vertical) / self.vertical.length_squared();
+        if !(0.0..1.0).contains(&s) || !(0.0..1.0).contains(&t) {
+            return None;
+        }
+        Some((s, t, pdf_dir))
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (x, y) = self.sample_lens()?;
+        if self.vignetted(s, t, x, y) {
+            return None;
+        }
+        let orig = self.origin + self.lens_radius * (self.u * x + self.v * y);
+        let d = self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        let dir = match self.focus_point(&self.origin, &d) {
+            Some(p_focus) => p_focus - orig,
+            None => d,
+        };
+        Some(Ray::new(&orig, &dir, self.sample_time(t)))
+    }
+
+    fn shutter(&self) -> &Shutter {
+        &self.shutter
+    }
+
+    fn central_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let d = self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        Some(Ray::new(&self.origin, &d, self.sample_time(t)))
+    }
+
+    // With a rolling shutter each row is exposed at its own time, which a light
+    // path traced at the camera ray's time does not match.
+    fn has_importance(&self) -> bool {
+        self.shutter.readout == 0.0
+    }
+
+    // Importance emitted along r, normalized so that it integrates to one over
+    // the lens and the image, together with the image coordinates it lands on.
+    // The lens term follows the aperture's transmission.
+    fn we(&self, r: &Ray) -> Option<(Color, f64, f64)> {
+        let (s, t, pdf_dir) = self.raster(r)?;
+        let lens_pdf = self.lens_pdf(&r.origin());
+        if lens_pdf <= 0.0 {
+            return None;
+        }
+        if self.lens_radius > 0.0 {
+            let (x, y) = self.lens_local(&r.origin());
+            if self.vignetted(s, t, x, y) {
+                return None;
+            }
+        }
+        let cos_theta = Vec3::dot(&Vec3::unit_vector(&r.direction()), &self.forward());
+        let we = lens_pdf * pdf_dir / cos_theta;
+        Some((Color::new(we, we, we), s, t))
+    }
+
+    // Positional (area) and directional (solid angle) densities of get_ray producing r.
+    fn pdf_we(&self, r: &Ray) -> (f64, f64) {
+        match self.raster(r) {
+            Some((_, _, pdf_dir)) => (self.lens_pdf(&r.origin()), pdf_dir),
+            None => (0.0, 0.0),
         }
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * Vec3::random_in_unit_disk();
-        let offset = self.u * rd.x() + self.v * rd.y();
-        Ray {
-            orig: self.origin + offset,
-            dir: self.lower_left_corner + s * self.horizontal + t * self.vertical
-                - self.origin
-                - offset,
-            tm: rtweekend::random_range(self.time0, self.time1),
+    // Samples a point on the lens seen from p. Returns the ray leaving the lens
+    // towards p, the solid angle density at p, and the importance carried.
+    fn sample_wi(&self, p: &Point3, time: f64) -> Option<(Ray, f64, Color, f64, f64)> {
+        let (x, y) = self.sample_lens()?;
+        let p_lens = self.origin + self.lens_radius * (self.u * x + self.v * y);
+        let to_p = *p - p_lens;
+        let dist = to_p.length();
+        let r = Ray::new(&p_lens, &(to_p / dist), time);
+        let (we, s, t) = self.we(&r)?;
+        let cos_theta = Vec3::dot(&r.direction(), &self.forward());
+        let pdf = dist * dist * self.lens_pdf(&p_lens) / cos_theta;
+        Some((r, pdf, we, s, t))
+    }
+}
+
+// Carries a camera built at the origin, looking down -z with y up, along
+// keyframed position and orientation tracks evaluated at each ray's time.
+pub struct AnimatedCamera {
+    camera: Box<dyn Camera>,
+    position: Track<Point3>,
+    orientation: Track<Quat>,
+}
+
+impl AnimatedCamera {
+    pub fn new(camera: Box<dyn Camera>, position: Track<Point3>, orientation: Track<Quat>) -> Self {
+        AnimatedCamera {
+            camera,
+            position,
+            orientation,
         }
     }
+
+    // Carries a ray of the inner camera along with the pose at its time.
+    fn to_world(&self, mut r: Ray) -> Ray {
+        let orientation = self.orientation.at(r.time());
+        r.orig = self.position.at(r.time()) + orientation.rotate(&r.orig);
+        r.dir = orientation.rotate(&r.dir);
+        r
+    }
+
+    // Brings a point into the inner camera's frame with the inverse pose at time.
+    fn to_camera(&self, p: &Point3, time: f64) -> Point3 {
+        self.orientation.at(time).inverse().rotate(&(*p - self.position.at(time)))
+    }
+
+    fn ray_to_camera(&self, r: &Ray) -> Ray {
+        let inverse = self.orientation.at(r.time()).inverse();
+        Ray::new(&self.to_camera(&r.origin(), r.time()), &inverse.rotate(&r.direction()), r.time())
+    }
+}
+
+impl Camera for AnimatedCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.camera.get_ray(s, t).map(|r| self.to_world(r))
+    }
+
+    fn shutter(&self) -> &Shutter {
+        self.camera.shutter()
+    }
+
+    fn central_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        self.camera.central_ray(s, t).map(|r| self.to_world(r))
+    }
+
+    fn has_importance(&self) -> bool {
+        self.camera.has_importance()
+    }
+
+    // Importance and its densities do not change under the rigid motion, so
+    // rays are only carried into the inner camera's frame.
+    fn we(&self, r: &Ray) -> Option<(Color, f64, f64)> {
+        self.camera.we(&self.ray_to_camera(r))
+    }
+
+    fn pdf_we(&self, r: &Ray) -> (f64, f64) {
+        self.camera.pdf_we(&self.ray_to_camera(r))
+    }
+
+    fn sample_wi(&self, p: &Point3, time: f64) -> Option<(Ray, f64, Color, f64, f64)> {
+        let (r, pdf, we, s, t) = self.camera.sample_wi(&self.to_camera(p, time), time)?;
+        Some((self.to_world(r), pdf, we, s, t))
+    }
 }
@@ -0,0 +1,131 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::rtweekend::random;
+use crate::vec3::Color;
+
+// How film radiance is scaled before it is tone mapped, calibrated like a
+// digital camera: the saturation based ISO speed of ISO 12232 (q = 0.65) and
+// a reflected light meter constant of 12.5.
+#[derive(Clone, Copy)]
+pub enum Exposure {
+    // Radiance is written as is.
+    None,
+    // ISO speed, shutter open time in seconds and f-stop.
+    Manual { iso: f64, shutter: f64, f_stop: f64 },
+    // Metered from the image's log-average luminance.
+    Auto,
+    // Exposure value at ISO 100 metered on another image, such as the first
+    // frame of a sequence.
+    Metered(f64),
+}
+
+pub struct Film {
+    pub width: i32,
+    pub height: i32,
+    pixels: Vec<Color>,
+    exposure: Exposure,
+    // In stops, on top of the exposure.
+    compensation: f64,
+}
+
+impl Film {
+    pub fn new(width: i32, height: i32) -> Self {
+        Film {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize],
+            exposure: Exposure::None,
+            compensation: 0.0,
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: Exposure, compensation: f64) -> Self {
+        self.exposure = exposure;
+        self.compensation = compensation;
+        self
+    }
+
+    // Exposure value at ISO 100 that a reflected light meter reads off the
+    // image's log-average luminance.
+    pub fn metered_ev100(&self, samples_per_pixel: i32) -> f64 {
+        let log_sum: f64 = self.pixels.iter()
+            .map(|p| (1e-4 + p.luminance() / samples_per_pixel as f64).ln())
+            .sum();
+        let log_average = (log_sum / self.pixels.len().max(1) as f64).exp();
+        (log_average * 100.0 / 12.5).log2()
+    }
+
+    // Factor taking average pixel radiance to values that clip at one.
+    fn exposure_scale(&self, samples_per_pixel: i32) -> f64 {
+        // Exposure value at ISO 100 of the settings, or of the scene as metered.
+        let ev100 = match self.exposure {
+            Exposure::None => return 2f64.powf(self.compensation),
+            Exposure::Manual { iso, shutter, f_stop } => (f_stop * f_stop / shutter * 100.0 / iso).log2(),
+            Exposure::Auto => self.metered_ev100(samples_per_pixel),
+            Exposure::Metered(ev100) => ev100,
+        };
+        // Luminance that saturates the sensor: 78 / (ISO q) N^2 / t.
+        let max_luminance = 78.0 / (100.0 * 0.65) * 2f64.powf(ev100);
+        2f64.powf(self.compensation) / max_luminance
+    }
+
+    // Pixel (0, 0) is the bottom-left corner, matching the camera's (s, t).
+    pub fn add_sample(&mut self, i: i32, j: i32, c: Color) {
+        self.pixels[(j * self.width + i) as usize] += c;
+    }
+
+    // Jittered image coordinates (s, t) inside pixel (i, j); add_splat maps
+    // them back to the same pixel.
+    pub fn sample_raster(&self, i: i32, j: i32) -> (f64, f64) {
+        (
+            (i as f64 + random()) / self.width as f64,
+            (j as f64 + random()) / self.height as f64,
+        )
+    }
+
+    // Accumulates a contribution that landed on image coordinates (s, t) in [0, 1).
+    pub fn add_splat(&mut self, s: f64, t: f64, c: Color) {
+        let i = ((s * self.width as f64) as i32).min(self.width - 1);
+        let j = ((t * self.height as f64) as i32).min(self.height - 1);
+        if i >= 0 && j >= 0 {
+            self.add_sample(i, j, c);
+        }
+    }
+
+    pub fn write_ppm(&self, samples_per_pixel: i32) {
+        let scale = self.exposure_scale(samples_per_pixel);
+        print!("P3\n{} {}\n255\n", self.width, self.height);
+        for j in (0..self.height).rev() {
+            for i in 0..self.width {
+                Color::write_color(scale * self.pixels[(j * self.width + i) as usize], samples_per_pixel);
+            }
+        }
+    }
+
+    // 8-bit RGB PNG, top row first, exposed and tone mapped like write_ppm.
+    // Written next to path with .tmp appended and renamed once complete, so
+    // an interrupted write never leaves a truncated file at path.
+    pub fn write_png(&self, path: &Path, samples_per_pixel: i32) -> io::Result<()> {
+        let scale = self.exposure_scale(samples_per_pixel);
+        let mut data = Vec::with_capacity((3 * self.width * self.height) as usize);
+        for j in (0..self.height).rev() {
+            for i in 0..self.width {
+                data.extend(Color::to_rgb8(scale * self.pixels[(j * self.width + i) as usize], samples_per_pixel));
+            }
+        }
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        let mut encoder = png::Encoder::new(&mut file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+        writer.finish()?;
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+}
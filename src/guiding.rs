@@ -0,0 +1,421 @@
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::hittable::Hittable;
+use crate::integrator::Integrator;
+use crate::ray::Ray;
+use crate::rtweekend::{random, PI};
+use crate::scene::Scene;
+use crate::vec3::{Color, Point3, Vec3};
+
+const MAX_DTREE_DEPTH: usize = 20;
+
+// Area preserving map between directions and the unit square.
+fn dir_to_canonical(d: &Vec3) -> (f64, f64) {
+    let cos_theta = d.z().clamp(-1.0, 1.0);
+    let mut phi = d.y().atan2(d.x());
+    if phi < 0.0 {
+        phi += 2.0 * PI;
+    }
+    (((cos_theta + 1.0) / 2.0).min(0.999999), (phi / (2.0 * PI)).min(0.999999))
+}
+
+fn canonical_to_dir(p: (f64, f64)) -> Vec3 {
+    let cos_theta = 2.0 * p.0 - 1.0;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * p.1;
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+#[derive(Clone, Default)]
+struct QuadNode {
+    sum: [f64; 4],
+    // Index of the node refining each quadrant, 0 for leaves (the root is
+    // never anyone's child).
+    children: [usize; 4],
+}
+
+// Directional distribution stored as a quadtree over the canonical square.
+#[derive(Clone)]
+struct DTree {
+    nodes: Vec<QuadNode>,
+}
+
+impl DTree {
+    fn new() -> Self {
+        DTree {
+            nodes: vec![QuadNode::default()],
+        }
+    }
+
+    fn total(&self) -> f64 {
+        self.nodes[0].sum.iter().sum()
+    }
+
+    // Quadrant of p, with p rescaled to the quadrant's own unit square.
+    fn quadrant(p: &mut (f64, f64)) -> usize {
+        let mut q = 0;
+        if p.0 >= 0.5 {
+            q |= 1;
+            p.0 -= 0.5;
+        }
+        if p.1 >= 0.5 {
+            q |= 2;
+            p.1 -= 0.5;
+        }
+        p.0 *= 2.0;
+        p.1 *= 2.0;
+        q
+    }
+
+    fn record(&mut self, mut p: (f64, f64), value: f64) {
+        let mut i = 0;
+        loop {
+            let q = Self::quadrant(&mut p);
+            self.nodes[i].sum[q] += value;
+            match self.nodes[i].children[q] {
+                0 => return,
+                c => i = c,
+            }
+        }
+    }
+
+    // Density over the canonical square.
+    fn pdf(&self, mut p: (f64, f64)) -> f64 {
+        if self.total() <= 0.0 {
+            return 1.0;
+        }
+        let mut i = 0;
+        let mut pdf = 1.0;
+        loop {
+            let node = &self.nodes[i];
+            let node_total: f64 = node.sum.iter().sum();
+            if node_total <= 0.0 {
+                return 0.0;
+            }
+            let q = Self::quadrant(&mut p);
+            pdf *= 4.0 * node.sum[q] / node_total;
+            match node.children[q] {
+                0 => return pdf,
+                c => i = c,
+            }
+        }
+    }
+
+    fn sample(&self, mut u: (f64, f64)) -> (f64, f64) {
+        if self.total() <= 0.0 {
+            return u;
+        }
+        let mut i = 0;
+        let mut origin = (0.0, 0.0);
+        let mut size = 1.0;
+        loop {
+            let s = &self.nodes[i].sum;
+            let mut q = 0;
+
+            let p_left = (s[0] + s[2]) / (s[0] + s[1] + s[2] + s[3]);
+            if u.0 < p_left {
+                u.0 /= p_left;
+            } else {
+                u.0 = (u.0 - p_left) / (1.0 - p_left);
+                q |= 1;
+            }
+            let (bottom, top) = (s[q], s[q | 2]);
+            let p_bottom = bottom / (bottom + top);
+            if u.1 < p_bottom {
+                u.1 /= p_bottom;
+            } else {
+                u.1 = (u.1 - p_bottom) / (1.0 - p_bottom);
+                q |= 2;
+            }
+            u = (u.0.min(0.999999), u.1.min(0.999999));
+
+            size *= 0.5;
+            origin.0 += (q & 1) as f64 * size;
+            origin.1 += (q >> 1) as f64 * size;
+            match self.nodes[i].children[q] {
+                0 => return (origin.0 + u.0 * size, origin.1 + u.1 * size),
+                c => i = c,
+            }
+        }
+    }
+
+    // Empty tree for the next iteration: quadrants that held more than
+    // threshold of the energy are refined one level further, the others are
+    // collapsed.
+    fn refined(&self, threshold: f64) -> DTree {
+        let mut out = DTree::new();
+        let total = self.total();
+        if total <= 0.0 {
+            return out;
+        }
+        let mut stack = vec![(0, 0, 1)];
+        while let Some((old, new, depth)) = stack.pop() {
+            for q in 0..4 {
+                if self.nodes[old].sum[q] / total <= threshold || depth >= MAX_DTREE_DEPTH {
+                    continue;
+                }
+                let child = out.nodes.len();
+                out.nodes.push(QuadNode::default());
+                out.nodes[new].children[q] = child;
+                if self.nodes[old].children[q] != 0 {
+                    stack.push((self.nodes[old].children[q], child, depth + 1));
+                }
+            }
+        }
+        out
+    }
+}
+
+struct SpatialNode {
+    // Axis the children split this node's box on, at its midpoint.
+    axis: usize,
+    // Index of the first of two children, 0 for leaves.
+    children: usize,
+    sampling: DTree,
+    building: DTree,
+    samples: usize,
+}
+
+// Spatial binary tree whose leaves hold a pair of directional quadtrees: one
+// learned during the previous iteration and sampled from, and one being
+// filled during the current iteration.
+struct SdTree {
+    min: Point3,
+    max: Point3,
+    nodes: Vec<SpatialNode>,
+}
+
+impl SdTree {
+    fn new() -> Self {
+        SdTree {
+            min: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY),
+            nodes: vec![SpatialNode {
+                axis: 0,
+                children: 0,
+                sampling: DTree::new(),
+                building: DTree::new(),
+                samples: 0,
+            }],
+        }
+    }
+
+    fn leaf(&self, p: &Point3) -> usize {
+        let mut min = self.min;
+        let mut max = self.max;
+        let mut i = 0;
+        while self.nodes[i].children != 0 {
+            let axis = self.nodes[i].axis;
+            let mid = 0.5 * (min[axis] + max[axis]);
+            if p[axis] < mid {
+                max.e[axis] = mid;
+                i = self.nodes[i].children;
+            } else {
+                min.e[axis] = mid;
+                i = self.nodes[i].children + 1;
+            }
+        }
+        i
+    }
+
+    fn record(&mut self, p: &Point3, dir: &Vec3, value: f64) {
+        // The bounds stay fixed once the root has been split.
+        if self.nodes.len() == 1 {
+            for a in 0..3 {
+                self.min.e[a] = self.min.e[a].min(p[a]);
+                self.max.e[a] = self.max.e[a].max(p[a]);
+            }
+        }
+        let leaf = self.leaf(p);
+        self.nodes[leaf].samples += 1;
+        self.nodes[leaf].building.record(dir_to_canonical(dir), value);
+    }
+
+    fn pdf(&self, p: &Point3, dir: &Vec3) -> f64 {
+        self.nodes[self.leaf(p)].sampling.pdf(dir_to_canonical(dir)) / (4.0 * PI)
+    }
+
+    fn sample(&self, p: &Point3) -> Vec3 {
+        canonical_to_dir(self.nodes[self.leaf(p)].sampling.sample((random(), random())))
+    }
+
+    // Ends an iteration: splits leaves that received more than max_samples
+    // records, then makes the collected distributions the sampling ones.
+    fn refine(&mut self, max_samples: f64, threshold: f64) {
+        let mut i = 0;
+        while i < self.nodes.len() {
+            if self.nodes[i].children == 0 && self.nodes[i].samples as f64 > max_samples {
+                let first = self.nodes.len();
+                let axis = self.nodes[i].axis;
+                for _ in 0..2 {
+                    self.nodes.push(SpatialNode {
+                        axis: (axis + 1) % 3,
+                        children: 0,
+                        sampling: DTree::new(),
+                        building: self.nodes[i].building.clone(),
+                        samples: self.nodes[i].samples / 2,
+                    });
+                }
+                self.nodes[i].children = first;
+            }
+            i += 1;
+        }
+
+        for node in self.nodes.iter_mut().filter(|node| node.children == 0) {
+            node.sampling = node.building.clone();
+            node.building = node.sampling.refined(threshold);
+            node.samples = 0;
+        }
+    }
+}
+
+struct GuideVertex {
+    p: Point3,
+    dir: Vec3,
+    // Path throughput after scattering into dir, and the density it was sampled with.
+    throughput: Color,
+    pdf: f64,
+    radiance: Color,
+}
+
+// Path tracer that learns the incident radiance field as it renders (Müller
+// et al., "Practical Path Guiding"). Iterations double their sample count;
+// each one samples directions at diffuse vertices from the SD-tree learned so
+// far, in a one-sample mixture with the BSDF, and records what its own paths
+// found into a refined tree for the next iteration.
+pub struct GuidedPathTracer {
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub guide_probability: f64,
+    pub spatial_threshold: f64,
+    pub directional_threshold: f64,
+}
+
+impl GuidedPathTracer {
+    fn radiance(&self, r: &Ray, scene: &Scene, tree: &SdTree, guided: bool, vertices: &mut Vec<GuideVertex>) -> Color {
+        let mut l = Color::new(0.0, 0.0, 0.0);
+        let mut beta = Color::new(1.0, 1.0, 1.0);
+        let mut r = *r;
+        vertices.clear();
+
+        for _ in 0..self.max_depth {
+            let rec = match scene.world.hit(&r, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    Self::add(&mut l, vertices, beta * scene.background(&r));
+                    break;
+                }
+            };
+            let (transmittance, scattered) = rec.mat.sample_interior(&r, &rec);
+            beta = beta * transmittance;
+            if let Some(scattered) = scattered {
+                r = scattered;
+                continue;
+            }
+            Self::add(&mut l, vertices, beta * rec.mat.emitted(&rec));
+
+            if rec.mat.is_specular() {
+                match rec.mat.scatter(&r, &rec) {
+                    Some((scattered, attenuation)) => {
+                        beta = beta * attenuation;
+                        r = scattered;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+
+            let wo = -Vec3::unit_vector(&r.direction());
+            let dir = if guided && random() < self.guide_probability {
+                tree.sample(&rec.p)
+            } else {
+                match rec.mat.scatter(&r, &rec) {
+                    Some((scattered, _)) => Vec3::unit_vector(&scattered.direction()),
+                    None => break,
+                }
+            };
+            let bsdf_pdf = rec.mat.pdf(&rec, &wo, &dir);
+            let pdf = if guided {
+                self.guide_probability * tree.pdf(&rec.p, &dir) + (1.0 - self.guide_probability) * bsdf_pdf
+            } else {
+                bsdf_pdf
+            };
+            if pdf <= 0.0 {
+                break;
+            }
+
+            let cos_theta = Vec3::dot(&dir, &rec.normal).abs();
+            beta = beta * rec.mat.eval(&rec, &wo, &dir) * (cos_theta / pdf);
+            if beta.near_zero() {
+                break;
+            }
+            vertices.push(GuideVertex {
+                p: rec.p,
+                dir,
+                throughput: beta,
+                pdf,
+                radiance: Color::new(0.0, 0.0, 0.0),
+            });
+            r = Ray {
+                orig: rec.p,
+                dir,
+                tm: r.time(),
+                wavelengths: r.wavelengths,
+            };
+        }
+        l
+    }
+
+    // Adds a contribution found by the path, crediting it to the incident
+    // radiance of every earlier guided vertex.
+    fn add(l: &mut Color, vertices: &mut [GuideVertex], c: Color) {
+        *l += c;
+        for v in vertices.iter_mut() {
+            for a in 0..3 {
+                if v.throughput[a] > 0.0 {
+                    v.radiance.e[a] += c[a] / v.throughput[a];
+                }
+            }
+        }
+    }
+}
+
+impl Integrator for GuidedPathTracer {
+    fn render(&self, scene: &Scene, cam: &dyn Camera, film: &mut Film) {
+        let mut tree = SdTree::new();
+        let mut vertices = Vec::new();
+        let mut remaining = self.samples_per_pixel;
+        let mut iteration = 0;
+
+        while remaining > 0 {
+            let spp = (1 << iteration).min(remaining);
+            eprint!("\rIteration {} with {} samples, {} remaining ", iteration, spp, remaining);
+
+            for j in 0..film.height {
+                for i in 0..film.width {
+                    let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+                    for _ in 0..spp {
+                        let (u, v) = film.sample_raster(i, j);
+
+                        let r = match cam.get_ray(u, v) {
+                            Some(r) => r,
+                            None => continue,
+                        };
+                        pixel_color += self.radiance(&r, scene, &tree, iteration > 0, &mut vertices);
+                        for vertex in &vertices {
+                            let value = vertex.radiance.luminance().max(0.0) / vertex.pdf;
+                            tree.record(&vertex.p, &vertex.dir, value);
+                        }
+                    }
+                    film.add_sample(i, j, pixel_color);
+                }
+            }
+
+            remaining -= spp;
+            let max_samples = self.spatial_threshold * ((1 << iteration) as f64).sqrt();
+            tree.refine(max_samples, self.directional_threshold);
+            iteration += 1;
+        }
+        eprintln!("\nDone.");
+    }
+}
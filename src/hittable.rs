@@ -12,6 +12,14 @@ pub struct HitRecord {
     pub normal: Vec3,
     pub mat: Rc<dyn Material>,
     pub t: f64,
+    // Time of the ray or sample, for animated materials.
+    pub time: f64,
+    pub u: f64,
+    pub v: f64,
+    // Partial derivatives of p with respect to u and v, spanning the tangent
+    // plane.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
     pub front_face: bool,
 }
 
@@ -28,4 +36,15 @@ impl HitRecord {
 
 pub trait Hittable {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    // Uniformly samples a point on the surface, returning a record whose normal
+    // points outward together with the area density of the sample.
+    fn sample_surface(&self, _time: f64) -> Option<(HitRecord, f64)> {
+        None
+    }
+
+    // Area density with which sample_surface would have produced p.
+    fn surface_pdf(&self, _p: &Point3, _time: f64) -> f64 {
+        0.0
+    }
 }
\ No newline at end of file
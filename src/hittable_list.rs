@@ -1,5 +1,7 @@
 use crate::hittable::{HitRecord, Hittable};
 use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::vec3::Point3;
 
 pub struct HittableList {
     pub objects: Vec<Box<dyn Hittable>>,
@@ -11,6 +13,7 @@ impl HittableList {
             objects: Vec::new(),
         }
     }
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.objects.clear();
     }
@@ -26,11 +29,32 @@ impl Hittable for HittableList {
         let mut closest_so_far = t_max;
 
         for object in &self.objects {
-            if let Some(rec) = object.hit(&r, t_min, closest_so_far) {
+            if let Some(rec) = object.hit(r, t_min, closest_so_far) {
                 opt_rec = Some(rec.clone());
                 closest_so_far = rec.t;
             }
         }
         opt_rec
     }
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let n = self.objects.len();
+        let index = ((random() * n as f64) as usize).min(n - 1);
+        let (rec, pdf) = self.objects[index].sample_surface(time)?;
+        Some((rec, pdf / n as f64))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.surface_pdf(p, time))
+            .sum();
+        sum / self.objects.len() as f64
+    }
 }
@@ -0,0 +1,68 @@
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::hittable::Hittable;
+use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::scene::Scene;
+use crate::spectrum::{self, Wavelengths};
+use crate::vec3::Color;
+
+pub trait Integrator {
+    fn render(&self, scene: &Scene, cam: &dyn Camera, film: &mut Film);
+}
+
+pub struct PathTracer {
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub spectral: bool,
+}
+
+impl PathTracer {
+    pub fn ray_color(r: &Ray, scene: &Scene, depth: i32) -> Color {
+        if depth <= 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        if let Some(rec) = scene.world.hit(r, 0.001, f64::INFINITY) {
+            let (transmittance, scattered) = rec.mat.sample_interior(r, &rec);
+            if let Some(scattered) = scattered {
+                return transmittance * Self::ray_color(&scattered, scene, depth - 1);
+            }
+            let emitted = spectrum::sample_rgb(rec.mat.emitted(&rec), r);
+            if let Some((scattered, attenuation)) = rec.mat.scatter(r, &rec) {
+                return transmittance * (emitted + attenuation * Self::ray_color(&scattered, scene, depth - 1));
+            }
+            return transmittance * emitted;
+        }
+
+        spectrum::sample_rgb(scene.background(r), r)
+    }
+}
+
+impl Integrator for PathTracer {
+    fn render(&self, scene: &Scene, cam: &dyn Camera, film: &mut Film) {
+        for j in (0..film.height).rev() {
+            eprint!("\rScanlines remaining: {} ", j);
+            for i in 0..film.width {
+                let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+                for _ in 0..self.samples_per_pixel {
+                    let (u, v) = film.sample_raster(i, j);
+
+                    let mut r = match cam.get_ray(u, v) {
+                        Some(r) => r,
+                        None => continue,
+                    };
+                    if self.spectral {
+                        let wavelengths = Wavelengths::sample(random());
+                        r.wavelengths = Some(wavelengths);
+                        pixel_color += wavelengths.to_rgb(Self::ray_color(&r, scene, self.max_depth));
+                    } else {
+                        pixel_color += Self::ray_color(&r, scene, self.max_depth);
+                    }
+                }
+                film.add_sample(i, j, pixel_color);
+            }
+        }
+        eprintln!("\nDone.");
+    }
+}
@@ -0,0 +1,126 @@
+use std::rc::Rc;
+
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::microfacet;
+use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+
+// Blend of two materials, showing b where the mask is 1 and a where it is 0.
+// Scattering picks one of the two at random with the mask as probability.
+pub struct Mix {
+    a: Rc<dyn Material>,
+    b: Rc<dyn Material>,
+    mask: Rc<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(a: Rc<dyn Material>, b: Rc<dyn Material>, mask: Rc<dyn Texture>) -> Self {
+        Mix { a, b, mask }
+    }
+
+    fn amount(&self, rec: &HitRecord) -> f64 {
+        self.mask.scalar(rec.u, rec.v, &rec.p, rec.time).clamp(0.0, 1.0)
+    }
+}
+
+impl Material for Mix {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        if random() < self.amount(rec) {
+            self.b.scatter(r_in, rec)
+        } else {
+            self.a.scatter(r_in, rec)
+        }
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let m = self.amount(rec);
+        (1.0 - m) * self.a.emitted(rec) + m * self.b.emitted(rec)
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        let m = self.amount(rec);
+        (1.0 - m) * self.a.eval(rec, wo, wi) + m * self.b.eval(rec, wo, wi)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> f64 {
+        let m = self.amount(rec);
+        (1.0 - m) * self.a.pdf(rec, wo, wi) + m * self.b.pdf(rec, wo, wi)
+    }
+
+    // The mixture can only be evaluated if neither side has a delta lobe.
+    fn is_specular(&self) -> bool {
+        self.a.is_specular() || self.b.is_specular()
+    }
+}
+
+// Internal reflections followed before light trapped in a coat is dropped.
+const MAX_COAT_BOUNCES: usize = 32;
+
+// Smooth clear dielectric coat over a base material. Light that enters the
+// coat bounces between the base and the underside of the coat until it
+// refracts back out; every bounce is followed explicitly, so the walk has no
+// closed form BSDF and the coated surface counts as specular.
+pub struct Coated {
+    base: Rc<dyn Material>,
+    ir: f64,
+}
+
+impl Coated {
+    pub fn new(base: Rc<dyn Material>, ir: f64) -> Self {
+        Coated { base, ir }
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        if !rec.front_face {
+            return self.base.scatter(r_in, rec);
+        }
+
+        let unit_direction = Vec3::unit_vector(&r_in.direction());
+        let cos_theta = Vec3::dot(&-unit_direction, &rec.normal);
+        let mut scattered = Ray {
+            orig: rec.p,
+            dir: Vec3::reflect(&unit_direction, &rec.normal),
+            tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
+        };
+        if random() < microfacet::fresnel_dielectric(cos_theta, self.ir) {
+            return Some((scattered, Color::new(1.0, 1.0, 1.0)));
+        }
+
+        // The coat is infinitely thin, so every bounce happens at rec.p.
+        let mut attenuation = Color::new(1.0, 1.0, 1.0);
+        scattered.dir = Vec3::refract(&unit_direction, &rec.normal, 1.0 / self.ir);
+        for _ in 0..MAX_COAT_BOUNCES {
+            let (up, base_attenuation) = self.base.scatter(&scattered, rec)?;
+            attenuation = attenuation * base_attenuation;
+            let up_direction = Vec3::unit_vector(&up.direction());
+            let cos_theta = Vec3::dot(&up_direction, &rec.normal);
+            if cos_theta <= 0.0 {
+                return None;
+            }
+
+            if random() < microfacet::fresnel_dielectric(cos_theta, 1.0 / self.ir) {
+                scattered = Ray {
+                    dir: Vec3::reflect(&up_direction, &rec.normal),
+                    ..up
+                };
+            } else {
+                let out = Ray {
+                    dir: Vec3::refract(&up_direction, &-rec.normal, self.ir),
+                    ..up
+                };
+                return Some((out, attenuation));
+            }
+        }
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+}
@@ -1,155 +1,312 @@
-mod vec3;
+use std::path::Path;
 use std::rc::Rc;
 
-use material::Material;
-use rtweekend::random_range;
-use vec3::Color;
+mod vec3;
 use vec3::Point3;
-use vec3::Vec3;
 
 mod ray;
 
 mod camera;
-use camera::Camera;
+
+mod animation;
+
+mod transform;
+use camera::ShutterProfile;
+
+mod aperture;
+use aperture::Aperture;
+
+mod projection;
+use projection::FisheyeMapping;
+
+mod stereo;
+
+mod realistic;
+use realistic::LensSystem;
 
 mod hittable;
-use hittable::Hittable;
 
 mod hittable_list;
-use hittable_list::HittableList;
 
 mod sphere;
-use sphere::Sphere;
 
 mod moving_sphere;
-use moving_sphere::MovingSphere;
+
+mod triangle;
+
+mod quad;
+
+mod masked;
 
 mod material;
-use material::{Dielectric, Lambertian, Metal};
+
+mod bump;
+
+mod layered;
+
+mod principled;
+
+mod subsurface;
+
+mod texture;
+use texture::ImageTexture;
+
+mod perlin;
+
+mod thin_film;
+
+mod microfacet;
+
+mod onb;
 
 mod rtweekend;
-use rtweekend::{random, INFINITY};
 
-fn ray_color(r: &ray::Ray, world: &HittableList, depth: i32) -> Color {
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
+mod spectrum;
 
-    if let Some(rec) = world.hit(&r, 0.001, INFINITY) {
-        if let Some((scattered, attenuation)) = rec.mat.scatter(r, &rec) {
-            return attenuation * ray_color(&scattered, world, depth - 1);
-        }
-        return Color::new(0.0, 0.0, 0.0);
-    }
+mod film;
+use film::{Exposure, Film};
 
-    let unit_direction = Vec3::unit_vector(&r.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
-}
+mod scene;
+use scene::{CameraSettings, Focus, Projection, Stereo};
 
-fn random_scene() -> HittableList {
-    let mut world = HittableList::new();
-
-    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Box::new(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    )));
-
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = random();
-            let center = Point3::new(a as f64 + 0.9 * random(), 0.2, b as f64 + 0.9 * random());
-
-            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
-                let object: Box<dyn Hittable> = if choose_mat < 0.8 {
-                    let albedo = Color::random() * Color::random();
-                    let center2 = center + Vec3::new(0.0, random_range(0.0, 0.5), 0.0);
-                    let mat = Rc::new(Lambertian::new(albedo));
-
-                    Box::new(MovingSphere::new(center, center2, 0.2, 0.0, 1.0, mat))
-                } else if choose_mat < 0.95 {
-                    let albedo = Color::random_range(0.5, 1.0);
-                    let fuzz = random_range(0.0, 0.5);
-                    let mat = Rc::new(Metal::new(albedo, fuzz));
-                    Box::new(Sphere::new(center, 0.2, mat))
-                } else {
-                    let mat = Rc::new(Dielectric::new(1.5));
-                    Box::new(Sphere::new(center, 0.2, mat))
-                };
-                world.add(object);
-            }
-        }
-    }
+mod scenes;
 
-    let material1 = Rc::new(Dielectric::new(1.5));
-    world.add(Box::new(Sphere::new(
-        Point3::new(0.0, 1.0, 0.0),
-        1.0,
-        material1,
-    )));
-    let material2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    world.add(Box::new(Sphere::new(
-        Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        material2,
-    )));
-    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
-    world.add(Box::new(Sphere::new(
-        Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        material3,
-    )));
-
-    world
-}
+mod integrator;
+use integrator::{Integrator, PathTracer};
+
+mod bdpt;
+use bdpt::Bdpt;
+
+mod photon_map;
+use photon_map::PhotonMapper;
+
+mod mlt;
+use mlt::Mlt;
+
+mod guiding;
+use guiding::GuidedPathTracer;
+
+mod options;
+use options::Options;
 
 fn main() {
+    let opts = Options::from_args();
+
     // Image
-    let aspect_ratio = 16.0 / 9.0;
     let image_width = 400;
-    let image_height = (image_width as f64 / aspect_ratio) as i32;
     let samples_per_pixel = 100;
     let max_depth = 50;
 
     // World
-    let world = random_scene();
+    let mut scene = match opts.scene.as_str() {
+        "random" => scenes::random_scene(),
+        "night" => scenes::night_scene(),
+        "materials" => scenes::materials_scene(),
+        "animated" => scenes::animated_scene(),
+        "shapes" => scenes::shapes_scene(),
+        name => Options::usage(&format!("unknown scene {}", name)),
+    };
 
     // Camera
-    let lookfrom = Point3::new(13.0, 2.0, 3.0);
-    let lookat = Point3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
-
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        20.0,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-        0.0,
-        1.0
-    );
-
-    print!("P3\n{} {}\n255\n", image_width, image_height);
-
-    for j in (0..image_height).rev() {
-        eprint!("\rScanlines remaining: {} ", j);
-        for i in 0..image_width {
-            let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..samples_per_pixel {
-                let u = (i as f64 + random()) / (image_width - 1) as f64;
-                let v = (j as f64 + random()) / (image_height - 1) as f64;
-
-                let r = cam.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, max_depth);
+    // Scenes that already have a thin lens keep it as it is.
+    let projection = match opts.projection.as_deref() {
+        Some("perspective") if matches!(scene.camera.projection, Projection::Perspective { .. }) => None,
+        name => name,
+    };
+    if let Some(name) = projection {
+        let distance = (scene.camera.lookfrom - scene.camera.lookat).length();
+        scene.camera.projection = match name {
+            "perspective" => Projection::Perspective {
+                focal_length: 68.0,
+                f_number: 6.8,
+                focus_dist: scene.camera.projection.focus_dist().unwrap_or(distance),
+            },
+            "orthographic" => Projection::Orthographic {
+                height: 2.0 * distance * rtweekend::degrees_to_radians(10.0).tan(),
+            },
+            "fisheye" => Projection::Fisheye {
+                fov: 180.0,
+                mapping: FisheyeMapping::Equidistant,
+            },
+            "equisolid" => Projection::Fisheye {
+                fov: 180.0,
+                mapping: FisheyeMapping::Equisolid,
+            },
+            "equirect" => Projection::Equirectangular,
+            name => Options::usage(&format!("unknown projection {}", name)),
+        };
+    }
+    if let Some(path) = &opts.lens {
+        let distance = (scene.camera.lookfrom - scene.camera.lookat).length();
+        scene.camera.projection = Projection::Realistic {
+            lens: LensSystem::from_file(path).unwrap_or_else(|e| Options::usage(&e.to_string())),
+            film_diagonal: 43.27,
+            focus_dist: scene.camera.projection.focus_dist().unwrap_or(distance),
+        };
+    }
+    if let Projection::Perspective { focal_length, f_number, .. } = &mut scene.camera.projection {
+        *focal_length = opts.focal_length.unwrap_or(*focal_length);
+        *f_number = opts.f_number.unwrap_or(*f_number);
+    } else if opts.focal_length.is_some() {
+        Options::usage("--focal-length needs a perspective camera");
+    } else if opts.f_number.is_some() && opts.iso.is_none() {
+        Options::usage("--f-number needs a perspective camera or --iso");
+    }
+    if let Some((x, y, z)) = opts.focus {
+        scene.camera.focus = Some(Focus::Point(Point3::new(x, y, z)));
+    }
+    if let Some((s, t)) = opts.focus_at {
+        scene.camera.focus = Some(Focus::Image(s, t));
+    }
+    if let Some(interocular) = opts.stereo {
+        let distance = (scene.camera.lookfrom - scene.camera.lookat).length();
+        scene.camera.stereo = Some(Stereo {
+            interocular,
+            convergence: opts.convergence.unwrap_or(distance),
+        });
+    }
+    if let Some(blades) = opts.blades {
+        scene.camera.aperture_shape = Aperture::polygon(blades, opts.blade_rotation);
+    }
+    if let Some(path) = &opts.aperture_mask {
+        let mask = ImageTexture::from_ppm(path).unwrap_or_else(|e| Options::usage(&e.to_string()));
+        scene.camera.aperture_shape = Aperture::mask(Rc::new(mask));
+    }
+    if let Some(cat_eye) = opts.cat_eye {
+        scene.camera.cat_eye = cat_eye;
+    }
+    if let Some(shift) = opts.shift {
+        scene.camera.lens_shift = shift;
+    }
+    if let Some(tilt) = opts.tilt {
+        scene.camera.focus_tilt = tilt;
+    }
+    if let Some(name) = &opts.shutter {
+        let profile = match name.as_str() {
+            "box" => ShutterProfile::Box,
+            "triangle" => ShutterProfile::Triangle,
+            "smooth" => ShutterProfile::Smooth {
+                ramp: opts.shutter_ramp.unwrap_or(0.25),
+            },
+            name => Options::usage(&format!("unknown shutter {}", name)),
+        };
+        scene.camera.shutter = scene.camera.shutter.with_profile(profile);
+    }
+    if let Some(readout) = opts.rolling {
+        scene.camera.shutter = scene.camera.shutter.with_rolling(readout);
+    }
+
+    // Omnidirectional stereo stacks two 2:1 panoramas into a square image.
+    let aspect_ratio = match (&scene.camera.projection, &scene.camera.stereo) {
+        (Projection::Equirectangular, Some(_)) => 1.0,
+        _ => 16.0 / 9.0,
+    };
+    let image_height = (image_width as f64 / aspect_ratio) as i32;
+
+    // Render
+    let integrator: Box<dyn Integrator> = match opts.integrator.as_str() {
+        "path" => Box::new(PathTracer {
+            samples_per_pixel,
+            max_depth,
+            spectral: opts.spectral,
+        }),
+        _ if opts.spectral => Options::usage("--spectral is only supported by the path integrator"),
+        "guided" => Box::new(GuidedPathTracer {
+            samples_per_pixel,
+            max_depth,
+            guide_probability: 0.5,
+            spatial_threshold: 12000.0,
+            directional_threshold: 0.01,
+        }),
+        "bdpt" => Box::new(Bdpt {
+            samples_per_pixel,
+            max_depth,
+        }),
+        "photon" => Box::new(PhotonMapper {
+            samples_per_pixel,
+            max_depth,
+            photons_per_pass: 20000,
+            initial_radius: 0.1,
+            alpha: 2.0 / 3.0,
+        }),
+        "mlt" => Box::new(Mlt {
+            samples_per_pixel,
+            max_depth,
+            n_bootstrap: 100000,
+            n_chains: 1000,
+            sigma: 0.01,
+            large_step_probability: 0.3,
+        }),
+        name => Options::usage(&format!("unknown integrator {}", name)),
+    };
+
+    // Exposed over the camera's shutter interval; cameras other than
+    // perspective ones take the f-stop from --f-number.
+    let film = |camera: &CameraSettings| {
+        let exposure = match opts.iso {
+            _ if opts.auto_exposure => Exposure::Auto,
+            None => Exposure::None,
+            Some(iso) => {
+                let f_stop = match camera.projection {
+                    Projection::Perspective { f_number, .. } => f_number,
+                    _ => opts.f_number.unwrap_or_else(|| Options::usage("--iso needs --f-number with this camera")),
+                };
+                Exposure::Manual {
+                    iso,
+                    shutter: camera.shutter.close - camera.shutter.open,
+                    f_stop,
+                }
             }
-            Color::write_color(pixel_color, samples_per_pixel);
+        };
+        Film::new(image_width, image_height).with_exposure(exposure, opts.ev)
+    };
+
+    let (first, last) = match opts.frames {
+        Some(frames) => frames,
+        None => {
+            scene.camera.autofocus(&scene.world, aspect_ratio);
+            let cam = scene.camera.build(aspect_ratio);
+            let mut film = film(&scene.camera);
+            integrator.render(&scene, cam.as_ref(), &mut film);
+            film.write_ppm(samples_per_pixel);
+            return;
+        }
+    };
+
+    // Sequence: frames already on disk are left alone, so an interrupted
+    // render picks up where it stopped. Auto exposure is metered on the first
+    // frame and held for the rest so that it does not flicker; a resumed
+    // render draws the first frame again only to meter it.
+    std::fs::create_dir_all(&opts.output).unwrap_or_else(|e| Options::usage(&e.to_string()));
+    let shutter = scene.camera.shutter;
+    let mut metered = None;
+    for frame in first..=last {
+        let path = Path::new(&opts.output).join(format!("frame_{:04}.png", frame));
+        let meter = opts.auto_exposure && frame == first;
+        if path.exists() && !meter {
+            eprintln!("Skipping {}", path.display());
+            continue;
+        }
+        if path.exists() {
+            eprintln!("Metering exposure on {}", path.display());
+        } else {
+            eprintln!("Frame {} of {}..{}", frame, first, last);
+        }
+        scene.camera.shutter = shutter.for_frame(frame, opts.fps, opts.shutter_angle);
+        scene.camera.autofocus(&scene.world, aspect_ratio);
+        let cam = scene.camera.build(aspect_ratio);
+        let mut film = film(&scene.camera);
+        integrator.render(&scene, cam.as_ref(), &mut film);
+        if meter {
+            metered = Some(film.metered_ev100(samples_per_pixel));
+        }
+        if let Some(ev100) = metered {
+            film = film.with_exposure(Exposure::Metered(ev100), opts.ev);
+        }
+        if path.exists() {
+            continue;
         }
+        film.write_png(&path, samples_per_pixel)
+            .unwrap_or_else(|e| Options::usage(&format!("{}: {}", path.display(), e)));
     }
-    eprintln!("\nDone.");
 }
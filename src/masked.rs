@@ -0,0 +1,70 @@
+use std::rc::Rc;
+
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::texture::Texture;
+use crate::vec3::Point3;
+
+pub enum AlphaMode {
+    // Cut out wherever alpha falls below the threshold.
+    Threshold(f64),
+    // Let rays through with probability 1 - alpha, for soft edges and
+    // partially transparent cards.
+    Stochastic,
+}
+
+// Cutout geometry: intersections where the alpha texture marks the surface as
+// transparent are skipped, and the ray carries on to whatever lies behind.
+pub struct Masked {
+    object: Box<dyn Hittable>,
+    alpha: Rc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl Masked {
+    pub fn new(object: Box<dyn Hittable>, alpha: Rc<dyn Texture>, mode: AlphaMode) -> Self {
+        Masked {
+            object,
+            alpha,
+            mode,
+        }
+    }
+
+    fn opaque(&self, rec: &HitRecord) -> bool {
+        let alpha = self.alpha.scalar(rec.u, rec.v, &rec.p, rec.time);
+        match self.mode {
+            AlphaMode::Threshold(threshold) => alpha >= threshold,
+            AlphaMode::Stochastic => random() < alpha,
+        }
+    }
+}
+
+impl Hittable for Masked {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut t_min = t_min;
+        loop {
+            let rec = self.object.hit(r, t_min, t_max)?;
+            if self.opaque(&rec) {
+                return Some(rec);
+            }
+            // Look for the object's next intersection beyond the cut out one.
+            t_min = rec.t + 1.0e-6;
+        }
+    }
+
+    // Samples the whole object and rejects points that are cut out, so an
+    // emitter only lights the scene from its opaque parts. The density stays
+    // that of the whole object and callers count a rejection as no light.
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let (rec, pdf) = self.object.sample_surface(time)?;
+        if !self.opaque(&rec) {
+            return None;
+        }
+        Some((rec, pdf))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        self.object.surface_pdf(p, time)
+    }
+}
@@ -1,12 +1,61 @@
 use crate::{
     hittable::HitRecord,
-    rtweekend,
+    microfacet::{self, TrowbridgeReitz},
+    onb::Onb,
+    rtweekend::{self, PI},
+    spectrum::{self, Wavelengths, LAMBDA_D},
+    thin_film::ThinFilm,
     vec3::{Color, Vec3},
 };
 
 use super::ray::Ray;
 pub trait Material {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)>;
+
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // BSDF value for light arriving from wi and leaving towards wo. Both
+    // directions are unit vectors pointing away from the surface.
+    fn eval(&self, _rec: &HitRecord, _wo: &Vec3, _wi: &Vec3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Solid angle density with which scatter picks wi when the ray leaves along wo.
+    fn pdf(&self, _rec: &HitRecord, _wo: &Vec3, _wi: &Vec3) -> f64 {
+        0.0
+    }
+
+    // Specular materials cannot be evaluated for arbitrary directions, so
+    // bidirectional connections must not end on them.
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    // Accounts for the medium enclosed by the surface along r, a ray that
+    // reached rec from inside. Returns the throughput weight of the segment,
+    // and the ray to follow instead of rec if the light scattered in the
+    // medium before getting there.
+    fn sample_interior(&self, _r: &Ray, _rec: &HitRecord) -> (Color, Option<Ray>) {
+        (Color::new(1.0, 1.0, 1.0), None)
+    }
+}
+
+// Beer-Lambert attenuation over the segment of r ending at rec, when that
+// segment lies inside a medium with the given absorption coefficient per unit
+// length.
+fn beer_lambert(absorption: Color, r: &Ray, rec: &HitRecord) -> Color {
+    if rec.front_face {
+        return Color::new(1.0, 1.0, 1.0);
+    }
+    let sigma = spectrum::sample_rgb(absorption, r);
+    let dist = rec.t * r.direction().length();
+    Color::new(
+        (-sigma.x() * dist).exp(),
+        (-sigma.y() * dist).exp(),
+        (-sigma.z() * dist).exp(),
+    )
 }
 
 pub struct Lambertian {
@@ -33,10 +82,111 @@ impl Material for Lambertian {
             orig: rec.p,
             dir: scatter_direction,
             tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
         };
-        let attenuation = self.albedo;
+        let attenuation = spectrum::sample_rgb(self.albedo, r_in);
         Some((scattered, attenuation))
     }
+
+    fn eval(&self, rec: &HitRecord, _wo: &Vec3, wi: &Vec3) -> Color {
+        if Vec3::dot(wi, &rec.normal) > 0.0 {
+            self.albedo / PI
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn pdf(&self, rec: &HitRecord, _wo: &Vec3, wi: &Vec3) -> f64 {
+        Vec3::dot(wi, &rec.normal).max(0.0) / PI
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+// Rough diffuse surface made of V-shaped Lambertian microfacets, in the
+// qualitative Oren-Nayar approximation. sigma is the standard deviation of the
+// facet slopes in degrees; 0 gives back Lambertian.
+pub struct OrenNayar {
+    albedo: Color,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        let sigma = rtweekend::degrees_to_radians(sigma);
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    // The BSDF divided by albedo / pi.
+    fn factor(&self, n: &Vec3, wo: &Vec3, wi: &Vec3) -> f64 {
+        let cos_i = Vec3::dot(wi, n);
+        let cos_o = Vec3::dot(wo, n);
+        if cos_i <= 0.0 || cos_o <= 0.0 {
+            return 0.0;
+        }
+        let sin_i = (1.0 - cos_i * cos_i).max(0.0).sqrt();
+        let sin_o = (1.0 - cos_o * cos_o).max(0.0).sqrt();
+
+        // Cosine of the azimuth between wi and wo.
+        let max_cos = if sin_i > 1.0e-4 && sin_o > 1.0e-4 {
+            let di = *wi - cos_i * *n;
+            let d_o = *wo - cos_o * *n;
+            (Vec3::dot(&di, &d_o) / (sin_i * sin_o)).max(0.0)
+        } else {
+            0.0
+        };
+        let (sin_alpha, tan_beta) = if cos_i > cos_o {
+            (sin_o, sin_i / cos_i)
+        } else {
+            (sin_i, sin_o / cos_o)
+        };
+        self.a + self.b * max_cos * sin_alpha * tan_beta
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let scatter_direction = rec.normal + Vec3::random_unit_vector();
+
+        let scatter_direction = if scatter_direction.near_zero() {
+            rec.normal
+        } else {
+            scatter_direction
+        };
+
+        let wo = -Vec3::unit_vector(&r_in.direction());
+        let wi = Vec3::unit_vector(&scatter_direction);
+        let scattered = Ray {
+            orig: rec.p,
+            dir: scatter_direction,
+            tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
+        };
+        // Cosine-weighted sampling cancels everything but albedo and the
+        // roughness factor.
+        let attenuation = spectrum::sample_rgb(self.albedo * self.factor(&rec.normal, &wo, &wi), r_in);
+        Some((scattered, attenuation))
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        self.albedo * (self.factor(&rec.normal, wo, wi) / PI)
+    }
+
+    fn pdf(&self, rec: &HitRecord, _wo: &Vec3, wi: &Vec3) -> f64 {
+        Vec3::dot(wi, &rec.normal).max(0.0) / PI
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
 }
 
 pub struct Metal {
@@ -60,8 +210,9 @@ impl Material for Metal {
             orig: rec.p,
             dir: reflected + self.fuzz * Vec3::random_in_unit_sphere(),
             tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
         };
-        let attenuation = self.albedo;
+        let attenuation = spectrum::sample_rgb(self.albedo, r_in);
         if Vec3::dot(&scattered.direction(), &rec.normal) > 0.0 {
             Some((scattered, attenuation))
         } else {
@@ -70,13 +221,172 @@ impl Material for Metal {
     }
 }
 
+// Rough metal described by a GGX microfacet distribution and a complex index
+// of refraction eta + ik per colour channel. Roughness alpha_x runs along the
+// tangent of the shading frame and alpha_y across it.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distrib: TrowbridgeReitz,
+    film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, alpha_x: f64, alpha_y: f64) -> Self {
+        Conductor {
+            eta,
+            k,
+            distrib: TrowbridgeReitz::new(alpha_x, alpha_y),
+            film: None,
+        }
+    }
+
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    // Fresnel reflectance in the space of the ray's colours.
+    fn fresnel(&self, rec: &HitRecord, cos_theta: f64, wavelengths: Option<&Wavelengths>) -> Color {
+        match &self.film {
+            Some(film) => film.reflectance(rec, cos_theta, 1.0, self.eta, self.k, wavelengths),
+            None => spectrum::sample_rgb_at(
+                microfacet::fresnel_conductor(cos_theta, &self.eta, &self.k),
+                wavelengths,
+            ),
+        }
+    }
+
+    fn f(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let wm = *wi + *wo;
+        if wm.near_zero() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let wm = Vec3::unit_vector(&wm);
+        let fresnel = self.fresnel(rec, Vec3::dot(wo, &wm).abs(), None);
+        fresnel * (self.distrib.d(&wm) * self.distrib.g(wo, wi) / (4.0 * wo.z() * wi.z()))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let frame = Onb::from_wu(&rec.normal, &rec.dpdu);
+        let wo = frame.to_local(&-Vec3::unit_vector(&r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let wavelengths = r_in.wavelengths.as_ref();
+        let (wi, attenuation) = if self.distrib.effectively_smooth() {
+            let wi = Vec3::new(-wo.x(), -wo.y(), wo.z());
+            (wi, self.fresnel(rec, wo.z(), wavelengths))
+        } else {
+            let wm = self.distrib.sample_wm(&wo, rtweekend::random(), rtweekend::random());
+            let wi = Vec3::reflect(&-wo, &wm);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            // f * cos / pdf with the visible normal pdf reduces to F G / G1.
+            let fresnel = self.fresnel(rec, Vec3::dot(&wo, &wm), wavelengths);
+            (wi, fresnel * (self.distrib.g(&wo, &wi) / self.distrib.g1(&wo)))
+        };
+
+        let scattered = Ray {
+            orig: rec.p,
+            dir: frame.to_world(&wi),
+            tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
+        };
+        Some((scattered, attenuation))
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        if self.distrib.effectively_smooth() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let frame = Onb::from_wu(&rec.normal, &rec.dpdu);
+        self.f(rec, &frame.to_local(wo), &frame.to_local(wi))
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> f64 {
+        if self.distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let frame = Onb::from_wu(&rec.normal, &rec.dpdu);
+        let wo = frame.to_local(wo);
+        let wi = frame.to_local(wi);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let wm = Vec3::unit_vector(&(wi + wo));
+        self.distrib.pdf(&wo, &wm) / (4.0 * Vec3::dot(&wo, &wm).abs())
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distrib.effectively_smooth()
+    }
+}
+
+// Index of refraction, optionally varying with wavelength (in nm).
+pub enum Ior {
+    Constant(f64),
+    // n = a + b / lambda^2, lambda in micrometres.
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i), lambda in micrometres.
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Ior {
+    pub fn at(&self, lambda: f64) -> f64 {
+        let l2 = (lambda * 1.0e-3) * (lambda * 1.0e-3);
+        match self {
+            Ior::Constant(n) => *n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+}
+
 pub struct Dielectric {
-    ir: f64, // Index of Refraction
+    ior: Ior,
+    absorption: Color,
+    film: Option<ThinFilm>,
 }
 
 impl Dielectric {
     pub fn new(ir: f64) -> Self {
-        Dielectric { ir }
+        Self::dispersive(Ior::Constant(ir))
+    }
+
+    pub fn dispersive(ior: Ior) -> Self {
+        Dielectric {
+            ior,
+            absorption: Color::new(0.0, 0.0, 0.0),
+            film: None,
+        }
+    }
+
+    // Tints the glass by absorbing light travelling inside it, with the given
+    // absorption coefficient per unit distance for each channel.
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Coats the outside of the glass with a thin film, e.g. soap.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
     }
 
     fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
@@ -89,11 +399,21 @@ impl Dielectric {
 
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
+        let mut attenuation = Color::new(1.0, 1.0, 1.0);
+        let mut wavelengths = r_in.wavelengths;
+        let ir = match wavelengths.as_mut() {
+            Some(w) => {
+                if self.ior.is_dispersive() {
+                    attenuation = w.terminate_secondary();
+                }
+                self.ior.at(w.hero())
+            }
+            None => self.ior.at(LAMBDA_D),
+        };
         let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
+            1.0 / ir
         } else {
-            self.ir
+            ir
         };
 
         let unit_direction = Vec3::unit_vector(&r_in.direction());
@@ -101,9 +421,28 @@ impl Material for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || Self::reflectance(cos_theta, refraction_ratio) > rtweekend::random()
-        {
+        let reflect = match &self.film {
+            Some(film) => {
+                // Reflectance differs per channel, so reflect with the average
+                // probability and reweight each channel.
+                let (outside, inside) = if rec.front_face { (1.0, ir) } else { (ir, 1.0) };
+                let zero = Color::new(0.0, 0.0, 0.0);
+                let inside = Color::new(inside, inside, inside);
+                let r = film.reflectance(rec, cos_theta, outside, inside, zero, wavelengths.as_ref());
+                let p = (r.x() + r.y() + r.z()) / 3.0;
+                let reflect = cannot_refract || rtweekend::random() < p;
+                if !cannot_refract {
+                    attenuation = if reflect {
+                        attenuation * r / p
+                    } else {
+                        attenuation * (Color::new(1.0, 1.0, 1.0) - r) / (1.0 - p)
+                    };
+                }
+                reflect
+            }
+            None => cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > rtweekend::random(),
+        };
+        let direction = if reflect {
             Vec3::reflect(&unit_direction, &rec.normal)
         } else {
             Vec3::refract(&unit_direction, &rec.normal, refraction_ratio)
@@ -113,7 +452,128 @@ impl Material for Dielectric {
             orig: rec.p,
             dir: direction,
             tm: r_in.time(),
+            wavelengths,
         };
         Some((scattered, attenuation))
     }
+
+    fn sample_interior(&self, r: &Ray, rec: &HitRecord) -> (Color, Option<Ray>) {
+        (beer_lambert(self.absorption, r, rec), None)
+    }
+}
+
+// Frosted glass: GGX microfacet reflection and transmission weighted by the
+// exact Fresnel equations. Like Dielectric, closed objects leave radiance
+// unscaled by the change in index, and the BSDF is symmetric for light
+// tracing.
+pub struct RoughDielectric {
+    ir: f64,
+    distrib: TrowbridgeReitz,
+    absorption: Color,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, alpha_x: f64, alpha_y: f64) -> Self {
+        RoughDielectric {
+            ir,
+            distrib: TrowbridgeReitz::new(alpha_x, alpha_y),
+            absorption: Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Relative index of refraction across the surface, seen from the side
+    // the normal points to.
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            self.ir
+        } else {
+            1.0 / self.ir
+        }
+    }
+
+    // Relative index and local directions for wo on either side of the
+    // surface, as bidirectional methods evaluate the reverse direction too.
+    fn local(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> (f64, Vec3, Vec3) {
+        let frame = Onb::from_wu(&rec.normal, &rec.dpdu);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() < 0.0 {
+            (1.0 / self.eta(rec), -wo, -wi)
+        } else {
+            (self.eta(rec), wo, wi)
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let frame = Onb::from_wu(&rec.normal, &rec.dpdu);
+        let wo = frame.to_local(&-Vec3::unit_vector(&r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let u = [rtweekend::random(), rtweekend::random(), rtweekend::random()];
+        let (wi, weight) = self.distrib.sample_dielectric(self.eta(rec), &wo, u)?;
+
+        let scattered = Ray {
+            orig: rec.p,
+            dir: frame.to_world(&wi),
+            tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
+        };
+        Some((scattered, Color::new(weight, weight, weight)))
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        if self.distrib.effectively_smooth() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let (eta, wo, wi) = self.local(rec, wo, wi);
+        let f = self.distrib.dielectric_f(eta, &wo, &wi);
+        Color::new(f, f, f)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> f64 {
+        if self.distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let (eta, wo, wi) = self.local(rec, wo, wi);
+        self.distrib.dielectric_pdf(eta, &wo, &wi)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distrib.effectively_smooth()
+    }
+
+    fn sample_interior(&self, r: &Ray, rec: &HitRecord) -> (Color, Option<Ray>) {
+        (beer_lambert(self.absorption, r, rec), None)
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(c: Color) -> Self {
+        DiffuseLight { emit: c }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.emit
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
 }
@@ -0,0 +1,221 @@
+use crate::rtweekend::PI;
+use crate::vec3::{Color, Vec3};
+
+// Trowbridge-Reitz (GGX) microfacet distribution with anisotropic roughness.
+// Directions are in the local shading frame, with the normal along z.
+pub struct TrowbridgeReitz {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+fn cos2_phi(w: &Vec3) -> f64 {
+    let sin2_theta = 1.0 - w.z() * w.z();
+    if sin2_theta <= 0.0 {
+        1.0
+    } else {
+        (w.x() * w.x() / sin2_theta).min(1.0)
+    }
+}
+
+fn tan2_theta(w: &Vec3) -> f64 {
+    (1.0 - w.z() * w.z()).max(0.0) / (w.z() * w.z())
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        TrowbridgeReitz { alpha_x, alpha_y }
+    }
+
+    // Below this roughness the surface is treated as a perfect mirror.
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1.0e-3
+    }
+
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        let tan2 = tan2_theta(wm);
+        if !tan2.is_finite() {
+            return 0.0;
+        }
+        let cos4 = wm.z() * wm.z() * wm.z() * wm.z();
+        let cos2 = cos2_phi(wm);
+        let e = tan2 * (cos2 / (self.alpha_x * self.alpha_x) + (1.0 - cos2) / (self.alpha_y * self.alpha_y));
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos4 * (1.0 + e) * (1.0 + e))
+    }
+
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let tan2 = tan2_theta(w);
+        if !tan2.is_finite() {
+            return 0.0;
+        }
+        let cos2 = cos2_phi(w);
+        let alpha2 = cos2 * self.alpha_x * self.alpha_x + (1.0 - cos2) * self.alpha_y * self.alpha_y;
+        ((1.0 + alpha2 * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    // Smith masking of a single direction.
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Smith height-correlated shadowing-masking.
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Density of the microfacet normals visible from w.
+    pub fn pdf(&self, w: &Vec3, wm: &Vec3) -> f64 {
+        self.g1(w) / w.z().abs() * self.d(wm) * Vec3::dot(w, wm).abs()
+    }
+
+    // Samples a microfacet normal visible from w (Heitz 2018).
+    pub fn sample_wm(&self, w: &Vec3, u1: f64, u2: f64) -> Vec3 {
+        let mut wh = Vec3::unit_vector(&Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()));
+        if wh.z() < 0.0 {
+            wh = -wh;
+        }
+        let t1 = if wh.z() < 0.99999 {
+            Vec3::unit_vector(&Vec3::cross(&Vec3::new(0.0, 0.0, 1.0), &wh))
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&wh, &t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let px = r * phi.cos();
+        let py = r * phi.sin();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z()) / 2.0;
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vec3::unit_vector(&Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1.0e-6)))
+    }
+}
+
+// Reflection and transmission through a dielectric interface with relative
+// index of refraction eta, wo being on the side the normal points to.
+// Transmission is scaled by 1 / eta, halfway between carrying radiance and
+// importance, which keeps it symmetric for light tracing; the scaling cancels
+// over entering and leaving a closed object.
+impl TrowbridgeReitz {
+    // Half vector of wo and wi, facing up, or None for configurations no
+    // microfacet can produce.
+    fn dielectric_half_vector(eta: f64, wo: &Vec3, wi: &Vec3) -> Option<Vec3> {
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return None;
+        }
+        let wm = if wi.z() > 0.0 { *wi + *wo } else { *wi * eta + *wo };
+        if wm.near_zero() {
+            return None;
+        }
+        let wm = Vec3::unit_vector(&wm);
+        let wm = if wm.z() < 0.0 { -wm } else { wm };
+        if Vec3::dot(&wm, wi) * wi.z() < 0.0 || Vec3::dot(&wm, wo) < 0.0 {
+            return None;
+        }
+        Some(wm)
+    }
+
+    pub fn dielectric_f(&self, eta: f64, wo: &Vec3, wi: &Vec3) -> f64 {
+        let wm = match Self::dielectric_half_vector(eta, wo, wi) {
+            Some(wm) => wm,
+            None => return 0.0,
+        };
+        let r = fresnel_dielectric(Vec3::dot(wo, &wm), eta);
+        let dg = self.d(&wm) * self.g(wo, wi);
+        if wi.z() > 0.0 {
+            dg * r / (4.0 * wo.z() * wi.z())
+        } else {
+            let denom = Vec3::dot(wi, &wm) + Vec3::dot(wo, &wm) / eta;
+            dg * (1.0 - r) * (Vec3::dot(wi, &wm) * Vec3::dot(wo, &wm) / (wi.z() * wo.z() * denom * denom)).abs() / eta
+        }
+    }
+
+    pub fn dielectric_pdf(&self, eta: f64, wo: &Vec3, wi: &Vec3) -> f64 {
+        let wm = match Self::dielectric_half_vector(eta, wo, wi) {
+            Some(wm) => wm,
+            None => return 0.0,
+        };
+        let r = fresnel_dielectric(Vec3::dot(wo, &wm), eta);
+        let pdf_wm = self.pdf(wo, &wm);
+        if wi.z() > 0.0 {
+            pdf_wm / (4.0 * Vec3::dot(wo, &wm).abs()) * r
+        } else {
+            let denom = Vec3::dot(wi, &wm) + Vec3::dot(wo, &wm) / eta;
+            pdf_wm * Vec3::dot(wi, &wm).abs() / (denom * denom) * (1.0 - r)
+        }
+    }
+
+    // Samples wi, returning it with f cos / pdf. Choosing reflection with
+    // probability R cancels the Fresnel term, and sampling visible normals
+    // leaves only G / G1 of the remaining factors, and 1 / eta for transmission.
+    pub fn sample_dielectric(&self, eta: f64, wo: &Vec3, u: [f64; 3]) -> Option<(Vec3, f64)> {
+        let smooth = self.effectively_smooth();
+        let wm = if smooth {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            self.sample_wm(wo, u[0], u[1])
+        };
+
+        let r = fresnel_dielectric(Vec3::dot(wo, &wm), eta);
+        let reflected = u[2] < r;
+        let wi = if reflected {
+            Vec3::reflect(&-*wo, &wm)
+        } else {
+            Vec3::refract(&-*wo, &wm, 1.0 / eta)
+        };
+        if (wi.z() > 0.0) != reflected {
+            return None;
+        }
+        let weight = match (smooth, reflected) {
+            (true, _) => 1.0,
+            (false, true) => self.g(wo, &wi) / self.g1(wo),
+            (false, false) => self.g(wo, &wi) / self.g1(wo) / eta,
+        };
+        Some((wi, weight))
+    }
+}
+
+// Fresnel reflectance of a conductor with complex index of refraction eta + ik,
+// evaluated per colour channel.
+pub fn fresnel_conductor(cos_theta_i: f64, eta: &Color, k: &Color) -> Color {
+    let c = cos_theta_i.clamp(0.0, 1.0);
+    let cos2 = c * c;
+    let sin2 = 1.0 - cos2;
+    let mut f = Color::new(0.0, 0.0, 0.0);
+    for i in 0..3 {
+        let eta2 = eta[i] * eta[i];
+        let k2 = k[i] * k[i];
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * c * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        f.e[i] = 0.5 * (rp + rs);
+    }
+    f
+}
+
+// Exact Fresnel reflectance of an interface between dielectrics, where eta is
+// the relative index of refraction of the side opposite the normal.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_i, eta) = if cos_theta_i < 0.0 {
+        (-cos_theta_i.max(-1.0), 1.0 / eta)
+    } else {
+        (cos_theta_i.min(1.0), eta)
+    };
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
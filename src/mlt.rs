@@ -0,0 +1,218 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::integrator::{Integrator, PathTracer};
+use crate::rtweekend::{self, PI};
+use crate::scene::Scene;
+use crate::vec3::Color;
+
+struct PrimarySample {
+    value: f64,
+    last_modified: i64,
+    value_backup: f64,
+    modify_backup: i64,
+}
+
+// Point in primary sample space, i.e. the sequence of values returned by
+// random() while a path is traced. Coordinates are created lazily and only
+// brought up to date with earlier large steps when they are read.
+struct MltSampler {
+    rng: StdRng,
+    sigma: f64,
+    large_step_probability: f64,
+    x: Vec<PrimarySample>,
+    current_iteration: i64,
+    large_step: bool,
+    last_large_step_iteration: i64,
+    sample_index: usize,
+}
+
+impl MltSampler {
+    fn new(seed: u64, sigma: f64, large_step_probability: f64) -> Self {
+        MltSampler {
+            rng: StdRng::seed_from_u64(seed),
+            sigma,
+            large_step_probability,
+            x: Vec::new(),
+            current_iteration: 0,
+            large_step: true,
+            last_large_step_iteration: 0,
+            sample_index: 0,
+        }
+    }
+
+    fn start_iteration(&mut self) {
+        self.current_iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < self.large_step_probability;
+        self.sample_index = 0;
+    }
+
+    fn next(&mut self) -> f64 {
+        let i = self.sample_index;
+        self.sample_index += 1;
+        self.ensure_ready(i);
+        self.x[i].value
+    }
+
+    fn ensure_ready(&mut self, i: usize) {
+        if i >= self.x.len() {
+            // A coordinate that was never read is still uniformly distributed,
+            // so it starts from a fresh value instead of being mutated. Doing
+            // otherwise would pile small steps around zero and stall the
+            // rejection sampling loops in Vec3.
+            let value = self.rng.gen();
+            self.x.push(PrimarySample {
+                value,
+                last_modified: self.current_iteration,
+                value_backup: value,
+                modify_backup: self.current_iteration,
+            });
+            return;
+        }
+        let xi = &mut self.x[i];
+
+        if xi.last_modified < self.last_large_step_iteration {
+            xi.value = self.rng.gen();
+            xi.last_modified = self.last_large_step_iteration;
+        }
+
+        xi.value_backup = xi.value;
+        xi.modify_backup = xi.last_modified;
+        if self.large_step {
+            xi.value = self.rng.gen();
+        } else {
+            // Catch up on every small step this coordinate missed at once.
+            let n_small = (self.current_iteration - xi.last_modified) as f64;
+            let u1: f64 = 1.0 - self.rng.gen::<f64>();
+            let u2: f64 = self.rng.gen();
+            let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+            xi.value += normal * self.sigma * n_small.sqrt();
+            xi.value -= xi.value.floor();
+        }
+        xi.last_modified = self.current_iteration;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step_iteration = self.current_iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for xi in self.x.iter_mut() {
+            if xi.last_modified == self.current_iteration {
+                xi.value = xi.value_backup;
+                xi.last_modified = xi.modify_backup;
+            }
+        }
+        self.current_iteration -= 1;
+    }
+}
+
+// Primary sample space Metropolis light transport (Kelemen et al.) on top of
+// the unidirectional path tracer. Chains wander over the random numbers used
+// by Camera::get_ray and Material::scatter, and every proposal is splatted onto
+// the film. The overall brightness is recovered from a bootstrap pass of
+// independent samples.
+pub struct Mlt {
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub n_bootstrap: usize,
+    pub n_chains: usize,
+    pub sigma: f64,
+    pub large_step_probability: f64,
+}
+
+impl Mlt {
+    fn l(&self, scene: &Scene, cam: &dyn Camera, sampler: &Rc<RefCell<MltSampler>>) -> (Color, f64, f64) {
+        let source = sampler.clone();
+        rtweekend::with_sample_source(Box::new(move || source.borrow_mut().next()), || {
+            let s = rtweekend::random();
+            let t = rtweekend::random();
+            let c = match cam.get_ray(s, t) {
+                Some(r) => PathTracer::ray_color(&r, scene, self.max_depth),
+                None => Color::new(0.0, 0.0, 0.0),
+            };
+            (c, s, t)
+        })
+    }
+
+    fn new_sampler(&self, seed: usize) -> Rc<RefCell<MltSampler>> {
+        Rc::new(RefCell::new(MltSampler::new(
+            seed as u64,
+            self.sigma,
+            self.large_step_probability,
+        )))
+    }
+}
+
+impl Integrator for Mlt {
+    fn render(&self, scene: &Scene, cam: &dyn Camera, film: &mut Film) {
+        // Bootstrap: estimate the image brightness b and seed the chains.
+        let mut bootstrap_weights = Vec::with_capacity(self.n_bootstrap);
+        for i in 0..self.n_bootstrap {
+            let (c, _, _) = self.l(scene, cam, &self.new_sampler(i));
+            bootstrap_weights.push(c.luminance());
+        }
+        let mut cdf = Vec::with_capacity(self.n_bootstrap);
+        let mut sum = 0.0;
+        for w in &bootstrap_weights {
+            sum += w;
+            cdf.push(sum);
+        }
+        let b = sum / self.n_bootstrap as f64;
+        if b <= 0.0 {
+            eprintln!("\nDone.");
+            return;
+        }
+
+        let total_mutations = self.samples_per_pixel as usize * (film.width * film.height) as usize;
+        let mutations_per_chain = total_mutations / self.n_chains;
+        let mut rng = StdRng::seed_from_u64(self.n_bootstrap as u64);
+
+        for chain in 0..self.n_chains {
+            eprint!("\rChains remaining: {} ", self.n_chains - chain);
+
+            let target = rng.gen::<f64>() * sum;
+            let index = cdf.partition_point(|&c| c <= target).min(self.n_bootstrap - 1);
+            let sampler = self.new_sampler(index);
+            let (mut current, mut current_s, mut current_t) = self.l(scene, cam, &sampler);
+
+            for _ in 0..mutations_per_chain {
+                sampler.borrow_mut().start_iteration();
+                let (proposed, proposed_s, proposed_t) = self.l(scene, cam, &sampler);
+
+                let current_y = current.luminance();
+                let proposed_y = proposed.luminance();
+                let accept = if current_y > 0.0 {
+                    (proposed_y / current_y).min(1.0)
+                } else {
+                    1.0
+                };
+
+                // Splat both states with their expected weights.
+                if accept > 0.0 && proposed_y > 0.0 {
+                    film.add_splat(proposed_s, proposed_t, proposed * (b * accept / proposed_y));
+                }
+                if current_y > 0.0 {
+                    film.add_splat(current_s, current_t, current * (b * (1.0 - accept) / current_y));
+                }
+
+                if rng.gen::<f64>() < accept {
+                    current = proposed;
+                    current_s = proposed_s;
+                    current_t = proposed_t;
+                    sampler.borrow_mut().accept();
+                } else {
+                    sampler.borrow_mut().reject();
+                }
+            }
+        }
+        eprintln!("\nDone.");
+    }
+}
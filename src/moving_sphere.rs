@@ -3,6 +3,8 @@ use std::rc::Rc;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::rtweekend::PI;
+use crate::sphere::Sphere;
 use crate::vec3::{Point3, Vec3};
 
 pub struct MovingSphere {
@@ -60,16 +62,49 @@ impl Hittable for MovingSphere {
 
         let p = r.at(root);
         let outward_normal = (p - self.center(r.time())) / self.radius;
+        let (u, v) = Sphere::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Sphere::get_sphere_tangents(&outward_normal, self.radius);
 
         let mut rec = HitRecord {
-            p: p,
+            p,
             normal: outward_normal,
             mat: self.mat.clone(),
             t: root,
+            time: r.time(),
+            u,
+            v,
+            dpdu,
+            dpdv,
             front_face: false,
         };
-        rec.set_face_normal(&r, &outward_normal);
+        rec.set_face_normal(r, &outward_normal);
 
         Some(rec)
     }
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let outward_normal = Vec3::random_unit_vector();
+        let (u, v) = Sphere::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Sphere::get_sphere_tangents(&outward_normal, self.radius);
+        let rec = HitRecord {
+            p: self.center(time) + self.radius * outward_normal,
+            normal: outward_normal,
+            mat: self.mat.clone(),
+            t: 0.0,
+            time,
+            u,
+            v,
+            dpdu,
+            dpdv,
+            front_face: true,
+        };
+        Some((rec, 1.0 / (4.0 * PI * self.radius * self.radius)))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        if ((*p - self.center(time)).length() - self.radius).abs() < 1.0e-6 * self.radius {
+            1.0 / (4.0 * PI * self.radius * self.radius)
+        } else {
+            0.0
+        }
+    }
 }
@@ -0,0 +1,47 @@
+use crate::vec3::Vec3;
+
+// Orthonormal basis around a surface normal w. Local coordinates have the
+// normal along z.
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    // Builds the frame with u tangent to circles around the world y axis, so
+    // anisotropic materials on a sphere line up along its latitudes.
+    pub fn from_w(n: &Vec3) -> Self {
+        let w = Vec3::unit_vector(n);
+        let a = if w.y().abs() > 0.999 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let u = Vec3::unit_vector(&Vec3::cross(&a, &w));
+        let v = Vec3::cross(&w, &u);
+        Onb { u, v, w }
+    }
+
+    // Builds the frame with u along the part of tangent t that lies in the
+    // surface, so anisotropic materials follow the surface parametrization.
+    // Falls back to from_w where t is degenerate.
+    pub fn from_wu(n: &Vec3, t: &Vec3) -> Self {
+        let w = Vec3::unit_vector(n);
+        let u = *t - Vec3::dot(&w, t) * w;
+        if u.near_zero() {
+            return Self::from_w(n);
+        }
+        let u = Vec3::unit_vector(&u);
+        let v = Vec3::cross(&w, &u);
+        Onb { u, v, w }
+    }
+
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(Vec3::dot(a, &self.u), Vec3::dot(a, &self.v), Vec3::dot(a, &self.w))
+    }
+
+    pub fn to_world(&self, a: &Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+}
@@ -0,0 +1,165 @@
+use std::str::FromStr;
+
+pub struct Options {
+    pub integrator: String,
+    pub scene: String,
+    pub projection: Option<String>,
+    pub lens: Option<String>,
+    pub focal_length: Option<f64>,
+    pub f_number: Option<f64>,
+    pub focus: Option<(f64, f64, f64)>,
+    pub focus_at: Option<(f64, f64)>,
+    pub stereo: Option<f64>,
+    pub convergence: Option<f64>,
+    pub blades: Option<u32>,
+    pub blade_rotation: f64,
+    pub aperture_mask: Option<String>,
+    pub cat_eye: Option<f64>,
+    pub shift: Option<(f64, f64)>,
+    pub tilt: Option<(f64, f64)>,
+    pub shutter: Option<String>,
+    pub shutter_ramp: Option<f64>,
+    pub rolling: Option<f64>,
+    pub frames: Option<(i32, i32)>,
+    pub fps: f64,
+    pub shutter_angle: f64,
+    pub output: String,
+    pub iso: Option<f64>,
+    pub auto_exposure: bool,
+    pub ev: f64,
+    pub spectral: bool,
+}
+
+impl Options {
+    pub fn from_args() -> Self {
+        let mut opts = Options {
+            integrator: "path".to_string(),
+            scene: "random".to_string(),
+            projection: None,
+            lens: None,
+            focal_length: None,
+            f_number: None,
+            focus: None,
+            focus_at: None,
+            stereo: None,
+            convergence: None,
+            blades: None,
+            blade_rotation: 0.0,
+            aperture_mask: None,
+            cat_eye: None,
+            shift: None,
+            tilt: None,
+            shutter: None,
+            shutter_ramp: None,
+            rolling: None,
+            frames: None,
+            fps: 24.0,
+            shutter_angle: 180.0,
+            output: ".".to_string(),
+            iso: None,
+            auto_exposure: false,
+            ev: 0.0,
+            spectral: false,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .unwrap_or_else(|| Self::usage(&format!("{} needs a value", name)))
+            };
+            match arg.as_str() {
+                "--integrator" => opts.integrator = value("--integrator"),
+                "--scene" => opts.scene = value("--scene"),
+                "--projection" => opts.projection = Some(value("--projection")),
+                "--lens" => opts.lens = Some(value("--lens")),
+                "--focal-length" => {
+                    opts.focal_length = Some(Self::number("--focal-length", &value("--focal-length")))
+                }
+                "--f-number" => opts.f_number = Some(Self::number("--f-number", &value("--f-number"))),
+                "--focus" => opts.focus = Some(Self::triple("--focus", &value("--focus"))),
+                "--focus-at" => opts.focus_at = Some(Self::pair("--focus-at", &value("--focus-at"))),
+                "--stereo" => opts.stereo = Some(Self::number("--stereo", &value("--stereo"))),
+                "--convergence" => {
+                    opts.convergence = Some(Self::number("--convergence", &value("--convergence")))
+                }
+                "--blades" => opts.blades = Some(Self::number("--blades", &value("--blades"))),
+                "--blade-rotation" => {
+                    opts.blade_rotation = Self::number("--blade-rotation", &value("--blade-rotation"))
+                }
+                "--aperture-mask" => opts.aperture_mask = Some(value("--aperture-mask")),
+                "--cat-eye" => opts.cat_eye = Some(Self::number("--cat-eye", &value("--cat-eye"))),
+                "--shift" => opts.shift = Some(Self::pair("--shift", &value("--shift"))),
+                "--tilt" => opts.tilt = Some(Self::pair("--tilt", &value("--tilt"))),
+                "--shutter" => {
+                    // "smooth:RAMP" gives the fraction of the exposure each ramp takes.
+                    let shutter = value("--shutter");
+                    match shutter.split_once(':') {
+                        Some(("smooth", ramp)) => {
+                            opts.shutter = Some("smooth".to_string());
+                            opts.shutter_ramp = Some(Self::number("--shutter", ramp));
+                        }
+                        _ => opts.shutter = Some(shutter),
+                    }
+                }
+                "--rolling" => opts.rolling = Some(Self::number("--rolling", &value("--rolling"))),
+                "--frames" => opts.frames = Some(Self::pair("--frames", &value("--frames"))),
+                "--fps" => opts.fps = Self::number("--fps", &value("--fps")),
+                "--shutter-angle" => {
+                    opts.shutter_angle = Self::number("--shutter-angle", &value("--shutter-angle"))
+                }
+                "--output" => opts.output = value("--output"),
+                "--iso" => opts.iso = Some(Self::number("--iso", &value("--iso"))),
+                "--auto-exposure" => opts.auto_exposure = true,
+                "--ev" => opts.ev = Self::number("--ev", &value("--ev")),
+                "--spectral" => opts.spectral = true,
+                _ => Self::usage(&format!("unknown option {}", arg)),
+            }
+        }
+        if opts.blades.is_some_and(|blades| blades < 3) {
+            Self::usage("--blades needs at least 3 blades");
+        }
+        if opts.shutter_ramp.is_some_and(|ramp| ramp <= 0.0 || ramp > 0.5) {
+            Self::usage("--shutter smooth:RAMP needs a ramp above 0 and at most 0.5");
+        }
+        if opts.frames.is_some_and(|(first, last)| first < 1 || last < first) {
+            Self::usage("--frames needs FIRST at least 1 and LAST no less than FIRST");
+        }
+        if opts.fps <= 0.0 {
+            Self::usage("--fps needs a positive number");
+        }
+        if opts.iso.is_some() && opts.auto_exposure {
+            Self::usage("--iso and --auto-exposure cannot be combined");
+        }
+        opts
+    }
+
+    fn number<T: FromStr>(name: &str, value: &str) -> T {
+        value
+            .parse()
+            .unwrap_or_else(|_| Self::usage(&format!("{} needs a number, got {}", name, value)))
+    }
+
+    // Two comma separated numbers, "x,y".
+    fn pair<T: FromStr>(name: &str, value: &str) -> (T, T) {
+        match value.split_once(',') {
+            Some((x, y)) => (Self::number(name, x), Self::number(name, y)),
+            None => Self::usage(&format!("{} needs two numbers as x,y, got {}", name, value)),
+        }
+    }
+
+    // Three comma separated numbers, "x,y,z".
+    fn triple(name: &str, value: &str) -> (f64, f64, f64) {
+        let numbers: Vec<&str> = value.split(',').collect();
+        match numbers[..] {
+            [x, y, z] => (Self::number(name, x), Self::number(name, y), Self::number(name, z)),
+            _ => Self::usage(&format!("{} needs three numbers as x,y,z, got {}", name, value)),
+        }
+    }
+
+    pub fn usage(msg: &str) -> ! {
+        eprintln!("{}", msg);
+        eprintln!("usage: ray-tracing-in-one-weekend [--integrator path|bdpt|photon|mlt|guided] [--scene random|night|materials|animated|shapes] [--projection perspective|orthographic|fisheye|equisolid|equirect | --lens FILE] [--focal-length MM] [--f-number N] [--focus X,Y,Z | --focus-at S,T] [--stereo INTEROCULAR [--convergence DISTANCE]] [--blades N [--blade-rotation DEGREES] | --aperture-mask FILE.ppm] [--cat-eye SHIFT] [--shift X,Y] [--tilt TILT,SWING] [--shutter box|triangle|smooth[:RAMP]] [--rolling READOUT] [--frames FIRST,LAST [--fps FPS] [--shutter-angle DEGREES] [--output DIR]] [--iso ISO | --auto-exposure] [--ev STOPS] [--spectral]");
+        std::process::exit(1);
+    }
+}
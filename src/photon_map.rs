@@ -0,0 +1,240 @@
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::hittable::{HitRecord, Hittable};
+use crate::integrator::Integrator;
+use crate::ray::Ray;
+use crate::rtweekend::{random, PI};
+use crate::scene::Scene;
+use crate::vec3::{Color, Point3, Vec3};
+
+pub struct Photon {
+    p: Point3,
+    // Direction the photon arrived from.
+    wi: Vec3,
+    power: Color,
+}
+
+// Photons stored as an implicit kd-tree: each subrange is split at its median
+// along the axis of largest extent, and the median's axis is kept alongside.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        // A degenerate scatter can leave a photon at a non-finite position,
+        // which no query can find and which would break the ordering.
+        photons.retain(|photon| (0..3).all(|a| photon.p[a].is_finite()));
+        let mut axes = vec![0; photons.len()];
+        Self::build(&mut photons, &mut axes);
+        PhotonMap { photons, axes }
+    }
+
+    fn build(photons: &mut [Photon], axes: &mut [usize]) {
+        if photons.len() <= 1 {
+            return;
+        }
+
+        let mut min = photons[0].p;
+        let mut max = photons[0].p;
+        for photon in photons.iter() {
+            for a in 0..3 {
+                min.e[a] = min.e[a].min(photon.p[a]);
+                max.e[a] = max.e[a].max(photon.p[a]);
+            }
+        }
+        let extent = max - min;
+        let axis = if extent.x() > extent.y() && extent.x() > extent.z() {
+            0
+        } else if extent.y() > extent.z() {
+            1
+        } else {
+            2
+        };
+
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+        axes[mid] = axis;
+
+        let (left, right) = photons.split_at_mut(mid);
+        let (left_axes, right_axes) = axes.split_at_mut(mid);
+        Self::build(left, left_axes);
+        Self::build(&mut right[1..], &mut right_axes[1..]);
+    }
+
+    // Calls f for every photon within radius of p.
+    pub fn for_each_near<F: FnMut(&Photon)>(&self, p: &Point3, radius: f64, mut f: F) {
+        self.query(0, self.photons.len(), p, radius * radius, &mut f);
+    }
+
+    fn query<F: FnMut(&Photon)>(&self, lo: usize, hi: usize, p: &Point3, radius_squared: f64, f: &mut F) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let photon = &self.photons[mid];
+        if (photon.p - *p).length_squared() <= radius_squared {
+            f(photon);
+        }
+
+        let d = p[self.axes[mid]] - photon.p[self.axes[mid]];
+        let (near, far) = if d < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.query(near.0, near.1, p, radius_squared, f);
+        if d * d <= radius_squared {
+            self.query(far.0, far.1, p, radius_squared, f);
+        }
+    }
+
+    // Density estimate of the radiance leaving rec towards wo.
+    pub fn estimate(&self, rec: &HitRecord, wo: &Vec3, radius: f64) -> Color {
+        let mut flux = Color::new(0.0, 0.0, 0.0);
+        self.for_each_near(&rec.p, radius, |photon| {
+            flux += rec.mat.eval(rec, wo, &photon.wi) * photon.power;
+        });
+        flux / (PI * radius * radius)
+    }
+}
+
+// Progressive photon mapping for caustics. Each pass emits a fresh set of
+// photons, keeps those that reached a diffuse surface through one or more
+// specular bounces, and traces one camera sample per pixel that uses the map
+// for the caustic part of the lighting. The gather radius shrinks from pass to
+// pass so the average of all passes converges.
+pub struct PhotonMapper {
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    pub photons_per_pass: usize,
+    pub initial_radius: f64,
+    pub alpha: f64,
+}
+
+impl PhotonMapper {
+    fn trace_photons(&self, scene: &Scene, cam: &dyn Camera) -> PhotonMap {
+        let mut photons = Vec::new();
+        for _ in 0..self.photons_per_pass {
+            // Photons follow the times of the whole frame, rolling shutter included.
+            let time = cam.sample_time(random());
+            // A failed sample still counts as an emitted photon carrying nothing,
+            // such as one landing on a cut out part of a masked emitter.
+            let (rec, pdf_pos) = match scene.lights.sample_surface(time) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let n = rec.normal;
+            let dir = n + Vec3::random_unit_vector();
+            let dir = if dir.near_zero() { n } else { dir };
+            // Cosine-weighted emission makes power independent of the direction.
+            let mut power = rec.mat.emitted(&rec) * PI / (pdf_pos * self.photons_per_pass as f64);
+            let mut r = Ray::new(&rec.p, &dir, time);
+            let mut specular_bounces = 0;
+
+            for _ in 0..self.max_depth {
+                let rec = match scene.world.hit(&r, 0.001, f64::INFINITY) {
+                    Some(rec) => rec,
+                    None => break,
+                };
+                let (transmittance, scattered) = rec.mat.sample_interior(&r, &rec);
+                power = power * transmittance;
+                if let Some(scattered) = scattered {
+                    r = scattered;
+                    continue;
+                }
+                if !rec.mat.is_specular() {
+                    if specular_bounces > 0 {
+                        photons.push(Photon {
+                            p: rec.p,
+                            wi: -Vec3::unit_vector(&r.direction()),
+                            power,
+                        });
+                    }
+                    break;
+                }
+                match rec.mat.scatter(&r, &rec) {
+                    Some((scattered, attenuation)) => {
+                        power = power * attenuation;
+                        r = scattered;
+                        specular_bounces += 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+        PhotonMap::new(photons)
+    }
+
+    fn radiance(&self, r: &Ray, scene: &Scene, caustics: &PhotonMap, radius: f64) -> Color {
+        let mut l = Color::new(0.0, 0.0, 0.0);
+        let mut beta = Color::new(1.0, 1.0, 1.0);
+        let mut r = *r;
+        let mut diffuse_seen = false;
+        // Set while the path is a diffuse vertex followed only by specular
+        // bounces; light found that way is already in the caustic map.
+        let mut caustic = false;
+
+        for _ in 0..self.max_depth {
+            let rec = match scene.world.hit(&r, 0.001, f64::INFINITY) {
+                Some(rec) => rec,
+                None => {
+                    l += beta * scene.background(&r);
+                    break;
+                }
+            };
+            let (transmittance, scattered) = rec.mat.sample_interior(&r, &rec);
+            beta = beta * transmittance;
+            if let Some(scattered) = scattered {
+                r = scattered;
+                continue;
+            }
+            if !caustic {
+                l += beta * rec.mat.emitted(&rec);
+            }
+
+            if rec.mat.is_specular() {
+                caustic = diffuse_seen;
+            } else {
+                let wo = -Vec3::unit_vector(&r.direction());
+                l += beta * caustics.estimate(&rec, &wo, radius);
+                diffuse_seen = true;
+                caustic = false;
+            }
+
+            match rec.mat.scatter(&r, &rec) {
+                Some((scattered, attenuation)) => {
+                    beta = beta * attenuation;
+                    r = scattered;
+                }
+                None => break,
+            }
+        }
+        l
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn render(&self, scene: &Scene, cam: &dyn Camera, film: &mut Film) {
+        let mut radius = self.initial_radius;
+        for pass in 0..self.samples_per_pixel {
+            eprint!("\rPasses remaining: {} ", self.samples_per_pixel - pass);
+            let caustics = self.trace_photons(scene, cam);
+            for j in 0..film.height {
+                for i in 0..film.width {
+                    let (u, v) = film.sample_raster(i, j);
+
+                    let c = match cam.get_ray(u, v) {
+                        Some(r) => self.radiance(&r, scene, &caustics, radius),
+                        None => Color::new(0.0, 0.0, 0.0),
+                    };
+                    film.add_sample(i, j, c);
+                }
+            }
+            let n = pass as f64 + 1.0;
+            radius *= ((n + self.alpha) / (n + 1.0)).sqrt();
+        }
+        eprintln!("\nDone.");
+    }
+}
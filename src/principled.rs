@@ -0,0 +1,283 @@
+use std::rc::Rc;
+
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::microfacet::TrowbridgeReitz;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{random, PI};
+use crate::spectrum;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{Color, Vec3};
+
+// Disney-style principled BSDF. A Burley diffuse base with sheen, a GGX
+// specular lobe that takes on the base colour as the surface turns metallic,
+// a rough glass lobe for transmission and a clearcoat on top. Every parameter
+// is a texture; scalar ones read the average channel.
+pub struct Principled {
+    pub base_color: Rc<dyn Texture>,
+    pub metallic: Rc<dyn Texture>,
+    pub roughness: Rc<dyn Texture>,
+    // Dielectric reflectance at normal incidence, scaled so that 0.5 is 4%.
+    pub specular: Rc<dyn Texture>,
+    pub sheen: Rc<dyn Texture>,
+    pub clearcoat: Rc<dyn Texture>,
+    pub clearcoat_roughness: Rc<dyn Texture>,
+    pub transmission: Rc<dyn Texture>,
+    pub emission: Rc<dyn Texture>,
+    pub ior: f64,
+}
+
+// Lobe weights and shapes evaluated at one hit point.
+struct Lobes {
+    base_color: Color,
+    f0: Color,
+    roughness: f64,
+    diffuse: f64,
+    sheen: f64,
+    specular: f64,
+    clearcoat: f64,
+    // Weights of the rough glass lobe's refraction and reflection.
+    transmission: f64,
+    glass_reflection: f64,
+    eta: f64,
+    distrib: TrowbridgeReitz,
+    coat_distrib: TrowbridgeReitz,
+}
+
+fn constant(value: f64) -> Rc<dyn Texture> {
+    Rc::new(SolidColor::gray(value))
+}
+
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+
+fn schlick(f0: Color, cos: f64) -> Color {
+    f0 + (Color::new(1.0, 1.0, 1.0) - f0) * schlick_weight(cos)
+}
+
+fn roughness_to_alpha(roughness: f64) -> f64 {
+    (roughness * roughness).max(1.0e-3)
+}
+
+impl Principled {
+    // A rough white plastic with the given base colour.
+    pub fn new(base_color: Rc<dyn Texture>) -> Self {
+        Principled {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            clearcoat_roughness: constant(0.03),
+            transmission: constant(0.0),
+            emission: Rc::new(SolidColor::gray(0.0)),
+            ior: 1.5,
+        }
+    }
+
+    // Lobes seen from outside the surface, or from inside it.
+    fn lobes(&self, rec: &HitRecord, outside: bool) -> Lobes {
+        let scalar = |tex: &Rc<dyn Texture>| tex.scalar(rec.u, rec.v, &rec.p, rec.time).clamp(0.0, 1.0);
+        let base_color = self.base_color.value(rec.u, rec.v, &rec.p, rec.time);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let transmission = scalar(&self.transmission);
+        let alpha = roughness_to_alpha(roughness);
+        let dielectric_f0 = 0.08 * scalar(&self.specular);
+
+        let mut lobes = Lobes {
+            base_color,
+            f0: (1.0 - metallic) * Color::new(dielectric_f0, dielectric_f0, dielectric_f0) + metallic * base_color,
+            roughness,
+            diffuse: (1.0 - metallic) * (1.0 - transmission),
+            sheen: scalar(&self.sheen),
+            specular: 1.0 - (1.0 - metallic) * transmission,
+            clearcoat: 0.25 * scalar(&self.clearcoat),
+            transmission: (1.0 - metallic) * transmission,
+            glass_reflection: (1.0 - metallic) * transmission,
+            eta: self.ior,
+            distrib: TrowbridgeReitz::new(alpha, alpha),
+            coat_distrib: TrowbridgeReitz::new(
+                roughness_to_alpha(scalar(&self.clearcoat_roughness)),
+                roughness_to_alpha(scalar(&self.clearcoat_roughness)),
+            ),
+        };
+        // Only light that was transmitted reaches the inside, where the
+        // surface is plain glass. Refraction keeps its weight so that it is
+        // the same in both directions.
+        if !outside {
+            lobes.diffuse = 0.0;
+            lobes.specular = 0.0;
+            lobes.clearcoat = 0.0;
+            lobes.glass_reflection = 1.0;
+            lobes.eta = 1.0 / self.ior;
+        }
+        lobes
+    }
+
+    // Lobes and local directions for wo on either side of the surface, as
+    // bidirectional methods evaluate the reverse direction too.
+    fn local(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> (Lobes, Vec3, Vec3) {
+        let frame = Onb::from_w(&rec.normal);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        if wo.z() < 0.0 {
+            (self.lobes(rec, !rec.front_face), -wo, -wi)
+        } else {
+            (self.lobes(rec, rec.front_face), wo, wi)
+        }
+    }
+}
+
+impl Lobes {
+    // Probabilities of sampling the diffuse, specular, clearcoat and
+    // transmission lobes, roughly proportional to their albedos.
+    fn selection(&self) -> [f64; 4] {
+        let weights = [
+            self.diffuse,
+            self.specular * (self.f0.luminance() + 0.1).min(1.0),
+            self.clearcoat * 0.15,
+            self.transmission,
+        ];
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return [0.0; 4];
+        }
+        weights.map(|w| w / total)
+    }
+
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        let mut f = Color::new(0.0, 0.0, 0.0);
+        if wo.z() <= 0.0 {
+            return f;
+        }
+
+        if wi.z() > 0.0 {
+            let wm = *wi + *wo;
+            if wm.near_zero() {
+                return f;
+            }
+            let wm = Vec3::unit_vector(&wm);
+            let cos_d = Vec3::dot(wi, &wm);
+
+            if self.diffuse > 0.0 {
+                let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+                let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z())) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z()));
+                let sheen = self.sheen * schlick_weight(cos_d);
+                f += self.diffuse * (self.base_color * (fd / PI) + Color::new(sheen, sheen, sheen));
+            }
+            if self.specular > 0.0 {
+                let dg = self.distrib.d(&wm) * self.distrib.g(wo, wi);
+                f += self.specular * schlick(self.f0, cos_d) * (dg / (4.0 * wo.z() * wi.z()));
+            }
+            if self.clearcoat > 0.0 {
+                let dg = self.coat_distrib.d(&wm) * self.coat_distrib.g(wo, wi);
+                let fc = 0.04 + 0.96 * schlick_weight(cos_d);
+                f += Color::new(1.0, 1.0, 1.0) * (self.clearcoat * fc * dg / (4.0 * wo.z() * wi.z()));
+            }
+        }
+
+        // Tinting each refraction by the square root of the base colour
+        // gives the full colour once light has passed through an object.
+        let (weight, tint) = if wi.z() < 0.0 {
+            (
+                self.transmission,
+                Color::new(self.base_color.x().sqrt(), self.base_color.y().sqrt(), self.base_color.z().sqrt()),
+            )
+        } else {
+            (self.glass_reflection, Color::new(1.0, 1.0, 1.0))
+        };
+        if weight > 0.0 {
+            f += tint * (weight * self.distrib.dielectric_f(self.eta, wo, wi));
+        }
+        f
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        let [p_diffuse, p_specular, p_clearcoat, p_transmission] = self.selection();
+        let mut pdf = 0.0;
+        if wi.z() > 0.0 {
+            let wm = *wi + *wo;
+            if !wm.near_zero() {
+                let wm = Vec3::unit_vector(&wm);
+                let jacobian = 4.0 * Vec3::dot(wo, &wm);
+                pdf += p_diffuse * wi.z() / PI;
+                pdf += p_specular * self.distrib.pdf(wo, &wm) / jacobian;
+                pdf += p_clearcoat * self.coat_distrib.pdf(wo, &wm) / jacobian;
+            }
+        }
+        pdf + p_transmission * self.distrib.dielectric_pdf(self.eta, wo, wi)
+    }
+
+    fn sample(&self, wo: &Vec3) -> Option<Vec3> {
+        let [p_diffuse, p_specular, p_clearcoat, _] = self.selection();
+        let u = random();
+        let wi = if u < p_diffuse {
+            let d = Vec3::new(0.0, 0.0, 1.0) + Vec3::random_unit_vector();
+            if d.near_zero() {
+                Vec3::new(0.0, 0.0, 1.0)
+            } else {
+                Vec3::unit_vector(&d)
+            }
+        } else if u < p_diffuse + p_specular {
+            Vec3::reflect(&-*wo, &self.distrib.sample_wm(wo, random(), random()))
+        } else if u < p_diffuse + p_specular + p_clearcoat {
+            Vec3::reflect(&-*wo, &self.coat_distrib.sample_wm(wo, random(), random()))
+        } else {
+            self.distrib.sample_dielectric(self.eta, wo, [random(), random(), random()])?.0
+        };
+        Some(wi)
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let frame = Onb::from_w(&rec.normal);
+        let wo = frame.to_local(&-Vec3::unit_vector(&r_in.direction()));
+        let lobes = self.lobes(rec, rec.front_face);
+
+        // One-sample MIS over the lobes: whichever one picked wi, weight by
+        // the full BSDF over the combined density.
+        let wi = lobes.sample(&wo)?;
+        let pdf = lobes.pdf(&wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let attenuation = lobes.f(&wo, &wi) * (wi.z().abs() / pdf);
+
+        let scattered = Ray {
+            orig: rec.p,
+            dir: frame.to_world(&wi),
+            tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
+        };
+        Some((scattered, spectrum::sample_rgb(attenuation, r_in)))
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.emission.value(rec.u, rec.v, &rec.p, rec.time)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        let (lobes, wo, wi) = self.local(rec, wo, wi);
+        lobes.f(&wo, &wi)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> f64 {
+        let (lobes, wo, wi) = self.local(rec, wo, wi);
+        lobes.pdf(&wo, &wi)
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
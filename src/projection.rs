@@ -0,0 +1,184 @@
+use crate::camera::{Camera, Shutter};
+use crate::ray::Ray;
+use crate::rtweekend::{self, PI};
+use crate::vec3::{Point3, Vec3};
+
+// Parallel rays through a window of the given height centered on lookfrom.
+pub struct OrthographicCamera {
+    lower_left_corner: Point3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    direction: Vec3,
+    shutter: Shutter,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        height: f64,
+        aspect_ratio: f64,
+        shutter: Shutter
+    ) -> Self {
+        let w = Vec3::unit_vector(&(lookfrom - lookat));
+        let u = Vec3::unit_vector(&Vec3::cross(&vup, &w));
+        let v = Vec3::cross(&w, &u);
+
+        let horizontal = aspect_ratio * height * u;
+        let vertical = height * v;
+        OrthographicCamera {
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: -w,
+            shutter,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let orig = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        Some(Ray::new(&orig, &self.direction, self.sample_time(t)))
+    }
+
+    fn shutter(&self) -> &Shutter {
+        &self.shutter
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum FisheyeMapping {
+    // Image radius proportional to the angle off the axis.
+    Equidistant,
+    // Image radius proportional to 2 sin(theta / 2), preserving solid angle.
+    Equisolid,
+}
+
+// Circular fisheye: the image circle is inscribed in the image height and
+// spans fov degrees; pixels outside it produce no ray.
+pub struct FisheyeCamera {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    half_fov: f64,
+    aspect_ratio: f64,
+    mapping: FisheyeMapping,
+    shutter: Shutter,
+}
+
+impl FisheyeCamera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        fov: f64, // field-of-view across the image circle in degrees
+        mapping: FisheyeMapping,
+        aspect_ratio: f64,
+        shutter: Shutter
+    ) -> Self {
+        let w = Vec3::unit_vector(&(lookfrom - lookat));
+        let u = Vec3::unit_vector(&Vec3::cross(&vup, &w));
+        let v = Vec3::cross(&w, &u);
+
+        FisheyeCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            half_fov: rtweekend::degrees_to_radians(fov.min(360.0)) / 2.0,
+            aspect_ratio,
+            mapping,
+            shutter,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.half_fov / 2.0).sin()).asin(),
+        };
+        let phi = y.atan2(x);
+        let dir = theta.sin() * (phi.cos() * self.u + phi.sin() * self.v) - theta.cos() * self.w;
+        Some(Ray::new(&self.origin, &dir, self.sample_time(t)))
+    }
+
+    fn shutter(&self) -> &Shutter {
+        &self.shutter
+    }
+}
+
+// 360 degree panorama: longitude across the image centered on lookat, latitude
+// from the bottom to the top, with vup as the pole.
+pub struct EquirectangularCamera {
+    origin: Point3,
+    forward: Vec3,
+    right: Vec3,
+    up: Vec3,
+    eye_offset: f64,
+    convergence: f64,
+    shutter: Shutter,
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Point3, lookat: Point3, vup: Vec3, shutter: Shutter) -> Self {
+        let up = Vec3::unit_vector(&vup);
+        let view = lookat - lookfrom;
+        let forward = Vec3::unit_vector(&(view - Vec3::dot(&view, &up) * up));
+        EquirectangularCamera {
+            origin: lookfrom,
+            forward,
+            right: Vec3::cross(&forward, &up),
+            up,
+            eye_offset: 0.0,
+            convergence: f64::INFINITY,
+            shutter,
+        }
+    }
+
+    // Omni-directional stereo: every ray starts on a circle of radius |offset|
+    // tangent to its direction (negative offsets for the left eye) and is toed
+    // in to cross the center line at the convergence distance. The circle
+    // shrinks towards the poles so that both eyes meet there.
+    pub fn with_eye(mut self, offset: f64, convergence: f64) -> Self {
+        self.eye_offset = offset;
+        self.convergence = convergence;
+        self
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+        let dir = latitude.cos() * (longitude.sin() * self.right + longitude.cos() * self.forward)
+            + latitude.sin() * self.up;
+        if self.eye_offset == 0.0 {
+            return Some(Ray::new(&self.origin, &dir, self.sample_time(t)));
+        }
+
+        let tangent = longitude.cos() * self.right - longitude.sin() * self.forward;
+        let offset = self.eye_offset * latitude.cos() * tangent;
+        let dir = if self.convergence.is_finite() {
+            self.convergence * dir - offset
+        } else {
+            dir
+        };
+        Some(Ray::new(&(self.origin + offset), &dir, self.sample_time(t)))
+    }
+
+    fn shutter(&self) -> &Shutter {
+        &self.shutter
+    }
+}
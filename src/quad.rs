@@ -0,0 +1,101 @@
+use std::rc::Rc;
+
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::vec3::{Point3, Vec3};
+
+// Parallelogram with corner q and edges u and v. Texture coordinates run from
+// 0 to 1 along each edge, and the outward normal is along u x v.
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    // Plane normal scaled so that dotting it with cross products against the
+    // edges gives coordinates in units of the edges.
+    w: Vec3,
+    area: f64,
+    mat: Rc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Rc<dyn Material>) -> Self {
+        let n = Vec3::cross(&u, &v);
+        Quad {
+            q,
+            u,
+            v,
+            normal: Vec3::unit_vector(&n),
+            w: n / Vec3::dot(&n, &n),
+            area: n.length(),
+            mat,
+        }
+    }
+
+    // Edge coordinates of a point in the plane.
+    fn coordinates(&self, p: &Point3) -> (f64, f64) {
+        let d = *p - self.q;
+        (
+            Vec3::dot(&self.w, &Vec3::cross(&d, &self.v)),
+            Vec3::dot(&self.w, &Vec3::cross(&self.u, &d)),
+        )
+    }
+
+    fn record(&self, p: Point3, a: f64, b: f64, t: f64, time: f64) -> HitRecord {
+        HitRecord {
+            p,
+            normal: self.normal,
+            mat: self.mat.clone(),
+            t,
+            time,
+            u: a,
+            v: b,
+            dpdu: self.u,
+            dpdv: self.v,
+            front_face: true,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = Vec3::dot(&self.normal, &r.direction());
+        if denom.abs() < 1.0e-8 {
+            return None;
+        }
+        let t = Vec3::dot(&self.normal, &(self.q - r.origin())) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+        let p = r.at(t);
+        let (a, b) = self.coordinates(&p);
+        if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+            return None;
+        }
+
+        let mut rec = self.record(p, a, b, t, r.time());
+        rec.set_face_normal(r, &self.normal);
+        Some(rec)
+    }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let (a, b) = (random(), random());
+        let p = self.q + a * self.u + b * self.v;
+        Some((self.record(p, a, b, 0.0, time), 1.0 / self.area))
+    }
+
+    fn surface_pdf(&self, p: &Point3, _time: f64) -> f64 {
+        if Vec3::dot(&(*p - self.q), &self.normal).abs() > 1.0e-6 * self.area.sqrt() {
+            return 0.0;
+        }
+        let (a, b) = self.coordinates(p);
+        let inside = |x: f64| (-1.0e-9..=1.0 + 1.0e-9).contains(&x);
+        if inside(a) && inside(b) {
+            1.0 / self.area
+        } else {
+            0.0
+        }
+    }
+}
@@ -1,18 +1,23 @@
+use crate::spectrum::Wavelengths;
 use crate::vec3::Point3;
 use crate::vec3::Vec3;
 
+#[derive(Clone, Copy)]
 pub struct Ray {
     pub orig: Point3,
     pub dir: Vec3,
     pub tm: f64,
+    // Set when the ray carries spectral samples instead of RGB.
+    pub wavelengths: Option<Wavelengths>,
 }
 
 impl Ray {
     pub fn new(origin: &Point3, direction: &Vec3, time: f64 ) -> Self {
         Self {
-            orig: origin.clone(),
-            dir: direction.clone(),
+            orig: *origin,
+            dir: *direction,
             tm: time,
+            wavelengths: None,
         }
     }
     pub fn origin(&self) -> Point3 {
@@ -0,0 +1,389 @@
+use std::fs;
+use std::io;
+
+use crate::camera::{Camera, Shutter};
+use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::scene::MM;
+use crate::vec3::{Point3, Vec3};
+
+// Film radii at which the chief ray and the exit pupil are tabulated.
+const FILM_SEGMENTS: usize = 64;
+// Rays across the rear element traced to bound the exit pupil at one radius.
+const PUPIL_GRID: usize = 64;
+
+#[derive(Clone, Copy)]
+pub struct LensElement {
+    // Positive when the surface is convex towards the scene; 0 marks the aperture stop.
+    pub curvature_radius: f64,
+    // Distance along the axis to the next surface towards the film.
+    pub thickness: f64,
+    // Index of refraction between this surface and the next, 0 meaning air.
+    pub eta: f64,
+    pub aperture_radius: f64,
+}
+
+// Spherical surfaces listed from the scene side to the film, in scene units.
+#[derive(Clone)]
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+}
+
+impl LensSystem {
+    // One surface per line: "radius thickness ior aperture-diameter" in
+    // millimetres, with '#' starting a comment. The thickness of the last
+    // surface is its distance to the film before focusing.
+    pub fn from_file(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut elements = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|v| v.parse().ok())
+                .collect::<Option<_>>()
+                .filter(|v: &Vec<f64>| v.len() == 4)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}:{}: expected radius, thickness, ior and aperture", path, line_number + 1),
+                    )
+                })?;
+            elements.push(LensElement {
+                curvature_radius: values[0] * MM,
+                thickness: values[1] * MM,
+                eta: values[2],
+                aperture_radius: values[3] * MM / 2.0,
+            });
+        }
+        if elements.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: no lens elements", path)));
+        }
+        Ok(LensSystem { elements })
+    }
+}
+
+fn medium(eta: f64) -> f64 {
+    if eta == 0.0 {
+        1.0
+    } else {
+        eta
+    }
+}
+
+fn refract(d: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+    let wi = -Vec3::unit_vector(d);
+    let n = if Vec3::dot(n, &wi) < 0.0 { -*n } else { *n };
+    let cos_i = Vec3::dot(&n, &wi);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i).max(0.0);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(eta * -wi + (eta * cos_i - cos_t) * n)
+}
+
+// Where a ray that entered parallel to the axis at height x leaves: the axial
+// positions of the principal plane and of the focal point on its way out.
+fn cardinal_points(x: f64, o: &Point3, d: &Vec3) -> (f64, f64) {
+    let tf = -o.x() / d.x();
+    let tp = (x - o.x()) / d.x();
+    ((*o + tp * *d).z(), (*o + tf * *d).z())
+}
+
+// Traces camera rays through a lens prescription onto the scene (Kolb et al.
+// 1995). Lens space has the film at z = 0 and the lens towards +z. The lens is
+// focused by moving it away from the film, so the field of view breathes with
+// the focus distance; distortion and vignetting come from the tracing.
+pub struct RealisticCamera {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    elements: Vec<LensElement>,
+    film_width: f64,
+    film_height: f64,
+    // Offset on the rear element plane of the ray through the center of the
+    // stop, by film radius.
+    chief_ray: Vec<f64>,
+    // Bounds (x0, x1, y1) on the rear element plane of the rays from a film
+    // point on the +x axis that get through the lens, by film radius. The
+    // pupil is symmetric about the axis, spanning -y1 to y1.
+    pupil: Vec<(f64, f64, f64)>,
+    // Largest bounds area sampled from any film point.
+    pupil_area: f64,
+    shutter: Shutter,
+}
+
+impl RealisticCamera {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Vec3,
+        lens: &LensSystem,
+        film_diagonal: f64, // millimetres
+        aspect_ratio: f64,
+        focus_dist: f64,
+        shutter: Shutter
+    ) -> Self {
+        let w = Vec3::unit_vector(&(lookfrom - lookat));
+        let u = Vec3::unit_vector(&Vec3::cross(&vup, &w));
+        let v = Vec3::cross(&w, &u);
+
+        let film_height = film_diagonal * MM / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let mut cam = RealisticCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            elements: lens.elements.clone(),
+            film_width: aspect_ratio * film_height,
+            film_height,
+            chief_ray: Vec::new(),
+            pupil: Vec::new(),
+            pupil_area: 0.0,
+            shutter,
+        };
+        cam.focus(focus_dist);
+        let film_radius = cam.film_radius();
+        let radius = |k: usize| k as f64 * film_radius / (FILM_SEGMENTS - 1) as f64;
+        cam.chief_ray = (0..FILM_SEGMENTS).map(|k| cam.chief_ray_offset(radius(k))).collect();
+        cam.pupil = (0..FILM_SEGMENTS).map(|k| cam.exit_pupil(radius(k))).collect();
+        cam.pupil_area = (0..FILM_SEGMENTS - 1)
+            .map(|k| {
+                let (x0, x1, y1) = cam.pupil_bounds(k);
+                (x1 - x0).max(0.0) * 2.0 * y1.max(0.0)
+            })
+            .fold(0.0, f64::max);
+        cam
+    }
+
+    fn film_radius(&self) -> f64 {
+        0.5 * (self.film_width * self.film_width + self.film_height * self.film_height).sqrt()
+    }
+
+    fn surface_z(&self, i: usize) -> f64 {
+        self.elements[i..].iter().map(|e| e.thickness).sum()
+    }
+
+    fn rear_z(&self) -> f64 {
+        self.surface_z(self.elements.len() - 1)
+    }
+
+    // Hit point and normal on surface i, optionally blocked by its aperture.
+    fn intersect(&self, i: usize, o: &Point3, d: &Vec3, clip: bool) -> Option<(Point3, Vec3)> {
+        let element = &self.elements[i];
+        let z = self.surface_z(i);
+        let (t, n) = if element.curvature_radius == 0.0 {
+            ((z - o.z()) / d.z(), Vec3::new(0.0, 0.0, 1.0))
+        } else {
+            let radius = element.curvature_radius;
+            let oc = *o - Point3::new(0.0, 0.0, z - radius);
+            let a = d.length_squared();
+            let half_b = Vec3::dot(&oc, d);
+            let c = oc.length_squared() - radius * radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            // The surface is the cap of the sphere facing its vertex at z.
+            let closer = (d.z() > 0.0) == (radius < 0.0);
+            let sqrtd = discriminant.sqrt();
+            let t = if closer { (-half_b - sqrtd) / a } else { (-half_b + sqrtd) / a };
+            (t, Vec3::unit_vector(&(oc + t * *d)))
+        };
+        if t.is_nan() || t <= 0.0 {
+            return None;
+        }
+        let p = *o + t * *d;
+        if clip && p.x() * p.x() + p.y() * p.y() > element.aperture_radius * element.aperture_radius {
+            return None;
+        }
+        Some((p, n))
+    }
+
+    // Traces a ray leaving the film through the surfaces down to last.
+    fn trace_from_film(&self, last: usize, o: Point3, d: Vec3, clip: bool) -> Option<(Point3, Vec3)> {
+        let (mut o, mut d) = (o, d);
+        for i in (last..self.elements.len()).rev() {
+            let (p, n) = self.intersect(i, &o, &d, clip)?;
+            o = p;
+            if self.elements[i].curvature_radius != 0.0 {
+                let eta_i = medium(self.elements[i].eta);
+                let eta_t = if i > 0 { medium(self.elements[i - 1].eta) } else { 1.0 };
+                d = refract(&d, &n, eta_i / eta_t)?;
+            }
+        }
+        Some((o, d))
+    }
+
+    fn trace_from_scene(&self, o: Point3, d: Vec3) -> Option<(Point3, Vec3)> {
+        let (mut o, mut d) = (o, d);
+        for i in 0..self.elements.len() {
+            let (p, n) = self.intersect(i, &o, &d, false)?;
+            o = p;
+            if self.elements[i].curvature_radius != 0.0 {
+                let eta_i = if i > 0 { medium(self.elements[i - 1].eta) } else { 1.0 };
+                let eta_t = medium(self.elements[i].eta);
+                d = refract(&d, &n, eta_i / eta_t)?;
+            }
+        }
+        Some((o, d))
+    }
+
+    // Moves the lens so that the plane at focus_dist from the film is sharp,
+    // using the thick lens approximation of the system.
+    fn focus(&mut self, focus_dist: f64) {
+        let x = 0.001 * self.film_radius();
+        let front = Point3::new(x, 0.0, self.surface_z(0) + 1.0);
+        let rear = Point3::new(x, 0.0, self.rear_z() - 1.0);
+        let (Some((o_film, d_film)), Some((o_scene, d_scene))) = (
+            self.trace_from_scene(front, Vec3::new(0.0, 0.0, -1.0)),
+            self.trace_from_film(0, rear, Vec3::new(0.0, 0.0, 1.0), false),
+        ) else {
+            return;
+        };
+        let (pz_film, fz_film) = cardinal_points(x, &o_film, &d_film);
+        let (pz_scene, _) = cardinal_points(x, &o_scene, &d_scene);
+        let focal_length = pz_film - fz_film;
+
+        // Image and object distances from the principal planes after moving
+        // the lens by delta must satisfy the lens equation. Planes closer than
+        // four focal lengths cannot be focused on and get the closest focus.
+        let a = pz_film;
+        let b = focus_dist - pz_scene;
+        let discriminant = ((a + b) * (a + b - 4.0 * focal_length)).max(0.0);
+        let delta = 0.5 * ((b - a) - discriminant.sqrt());
+        if let Some(rear) = self.elements.last_mut() {
+            rear.thickness += delta;
+        }
+    }
+
+    // Bounds of the exit pupil seen from film radius r, from a grid of rays
+    // aimed across the rear element plane and grown by one grid cell. Empty,
+    // with x0 above x1, when no ray gets through.
+    fn exit_pupil(&self, r: f64) -> (f64, f64, f64) {
+        let z = self.rear_z();
+        let extent = 1.5 * self.elements[self.elements.len() - 1].aperture_radius;
+        let cell = 2.0 * extent / PUPIL_GRID as f64;
+        let film = Point3::new(r, 0.0, 0.0);
+        let (mut x0, mut x1, mut y1) = (f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for i in 0..PUPIL_GRID {
+            for j in 0..PUPIL_GRID / 2 {
+                let x = -extent + (i as f64 + 0.5) * cell;
+                let y = (j as f64 + 0.5) * cell;
+                if self.trace_from_film(0, film, Vec3::new(x - r, y, z), true).is_some() {
+                    x0 = x0.min(x);
+                    x1 = x1.max(x);
+                    y1 = y1.max(y);
+                }
+            }
+        }
+        (x0 - cell, x1 + cell, y1 + cell)
+    }
+
+    // Pupil bounds covering the film radii of segment k.
+    fn pupil_bounds(&self, k: usize) -> (f64, f64, f64) {
+        let ((a0, a1, ay), (b0, b1, by)) = (self.pupil[k], self.pupil[k + 1]);
+        (a0.min(b0), a1.max(b1), ay.max(by))
+    }
+
+    // Solves for the point on the rear element plane that a ray from film
+    // radius r must aim at to cross the center of the aperture stop.
+    fn chief_ray_offset(&self, r: f64) -> f64 {
+        let Some(stop) = self.elements.iter().position(|e| e.curvature_radius == 0.0) else {
+            return 0.0;
+        };
+        let z = self.rear_z();
+        let film = Point3::new(r, 0.0, 0.0);
+        let height = |x: f64| {
+            self.trace_from_film(stop, film, Vec3::new(x - r, 0.0, z), false)
+                .map(|(p, _)| p.x())
+        };
+        let step = 0.01 * self.elements[self.elements.len() - 1].aperture_radius;
+        let mut x = 0.0;
+        for _ in 0..8 {
+            let (Some(h0), Some(h1)) = (height(x), height(x + step)) else {
+                break;
+            };
+            if h1 == h0 {
+                break;
+            }
+            x -= h0 * step / (h1 - h0);
+        }
+        x
+    }
+
+    // Table segment holding film radius r, and the position within it.
+    fn segment(&self, r: f64) -> (usize, f64) {
+        let segment = r / self.film_radius() * (FILM_SEGMENTS - 1) as f64;
+        let k = (segment as usize).min(FILM_SEGMENTS - 2);
+        (k, (segment - k as f64).min(1.0))
+    }
+
+    // Point on the film for image coordinates (s, t), and the offset on the
+    // rear element plane of the chief ray leaving it.
+    fn film_point(&self, s: f64, t: f64) -> (Point3, Vec3) {
+        // The lens forms an inverted image on the film.
+        let x = (0.5 - s) * self.film_width;
+        let y = (0.5 - t) * self.film_height;
+        let r = (x * x + y * y).sqrt();
+
+        let (k, f) = self.segment(r);
+        let chief = (1.0 - f) * self.chief_ray[k] + f * self.chief_ray[k + 1];
+        let (cx, cy) = if r > 0.0 { (chief * x / r, chief * y / r) } else { (0.0, 0.0) };
+        (Point3::new(x, y, 0.0), Vec3::new(cx, cy, 0.0))
+    }
+
+    // Ray leaving the front of the lens in lens space, carried into the scene.
+    fn to_scene(&self, o: Point3, d: Vec3, t: f64) -> Ray {
+        let orig = self.origin + o.x() * self.u + o.y() * self.v - o.z() * self.w;
+        let dir = d.x() * self.u + d.y() * self.v - d.z() * self.w;
+        Ray::new(&orig, &dir, self.sample_time(t))
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (film, _) = self.film_point(s, t);
+        let r = (film.x() * film.x() + film.y() * film.y()).sqrt();
+        let (x0, x1, y1) = self.pupil_bounds(self.segment(r).0);
+        let area = (x1 - x0).max(0.0) * 2.0 * y1.max(0.0);
+        if area <= 0.0 {
+            return None;
+        }
+        // Uniform within the pupil bounds, turned from the +x axis towards the
+        // film point.
+        let (cos_phi, sin_phi) = if r > 0.0 { (film.x() / r, film.y() / r) } else { (1.0, 0.0) };
+        let (px, py) = (x0 + random() * (x1 - x0), (2.0 * random() - 1.0) * y1);
+        let rear = Point3::new(cos_phi * px - sin_phi * py, sin_phi * px + cos_phi * py, self.rear_z());
+        let d = rear - film;
+
+        // Falloff with the fourth power of the cosine off the axis, applied by
+        // discarding rays. Keeping samples in proportion to the area of the
+        // bounds makes every film point sample the rear element plane with the
+        // same density.
+        let cos_theta = d.z() / d.length();
+        if random() * self.pupil_area > cos_theta.powi(4) * area {
+            return None;
+        }
+        let (o, d) = self.trace_from_film(0, film, d, true)?;
+        Some(self.to_scene(o, d, t))
+    }
+
+    fn shutter(&self) -> &Shutter {
+        &self.shutter
+    }
+
+    // The chief ray, through the center of the aperture stop.
+    fn central_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (film, chief) = self.film_point(s, t);
+        let d = Point3::new(chief.x(), chief.y(), self.rear_z()) - film;
+        let (o, d) = self.trace_from_film(0, film, d, false)?;
+        Some(self.to_scene(o, d, t))
+    }
+}
@@ -1,17 +1,33 @@
-use rand::{Rng, thread_rng};
-pub use std::f64::INFINITY;
+use std::cell::RefCell;
+
 pub use std::f64::consts::PI;
 
+thread_local! {
+    static SAMPLE_SOURCE: RefCell<Option<Box<dyn FnMut() -> f64>>> = RefCell::new(None);
+}
+
 pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
 pub fn random() -> f64 {
-    rand::random::<f64>()
+    SAMPLE_SOURCE.with(|source| match source.borrow_mut().as_mut() {
+        Some(next) => next(),
+        None => rand::random::<f64>(),
+    })
 }
 
 pub fn random_range(min: f64, max: f64) -> f64 {
-    thread_rng().gen_range(min..max)
+    min + (max - min) * random()
+}
+
+// Makes random() draw from source while f runs, so that an integrator can
+// control every random decision taken while a path is traced.
+pub fn with_sample_source<R>(source: Box<dyn FnMut() -> f64>, f: impl FnOnce() -> R) -> R {
+    let previous = SAMPLE_SOURCE.with(|s| s.replace(Some(source)));
+    let result = f();
+    SAMPLE_SOURCE.with(|s| s.replace(previous));
+    result
 }
 
 pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
@@ -22,4 +38,4 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     } else {
         x
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,240 @@
+use crate::animation::{Interpolation, Keyframe, Quat, Track};
+use crate::aperture::Aperture;
+use crate::camera::{AnimatedCamera, Camera, PerspectiveCamera, Shutter};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::projection::{
+    EquirectangularCamera, FisheyeCamera, FisheyeMapping, OrthographicCamera,
+};
+use crate::ray::Ray;
+use crate::realistic::{LensSystem, RealisticCamera};
+use crate::rtweekend;
+use crate::stereo::StereoCamera;
+use crate::vec3::{Color, Point3, Vec3};
+
+pub enum Background {
+    Sky,
+    Solid(Color),
+}
+
+// Scene units are decimetres, so a millimetre is a hundredth of one.
+pub const MM: f64 = 0.01;
+
+// Height of the full frame film that perspective focal lengths refer to, in
+// millimetres.
+const FILM_HEIGHT: f64 = 24.0;
+
+pub enum Projection {
+    // Thin lens of the given focal length in millimetres. An infinite f-number
+    // makes a pinhole.
+    Perspective { focal_length: f64, f_number: f64, focus_dist: f64 },
+    Orthographic { height: f64 },
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    Equirectangular,
+    // Traced through a lens prescription onto a film of the given diagonal in millimetres.
+    Realistic { lens: LensSystem, film_diagonal: f64, focus_dist: f64 },
+}
+
+impl Projection {
+    // Distance of the plane of focus, for projections with a lens.
+    pub fn focus_dist(&self) -> Option<f64> {
+        match self {
+            &Projection::Perspective { focus_dist, .. } | &Projection::Realistic { focus_dist, .. } => Some(focus_dist),
+            _ => None,
+        }
+    }
+}
+
+// Keyframed camera pose; orientations are interpolated with slerp.
+#[derive(Clone)]
+pub struct CameraMotion {
+    pub position: Track<Point3>,
+    pub orientation: Track<Quat>,
+}
+
+impl CameraMotion {
+    // Keyframes given as (time, lookfrom, lookat), sharing vup and the
+    // interpolation.
+    pub fn look_at(keys: &[(f64, Point3, Point3)], vup: Vec3, interpolation: Interpolation) -> Self {
+        let orientation = |lookfrom: Point3, lookat: Point3| {
+            let w = Vec3::unit_vector(&(lookfrom - lookat));
+            let u = Vec3::unit_vector(&Vec3::cross(&vup, &w));
+            Quat::from_basis(&u, &Vec3::cross(&w, &u), &w)
+        };
+        CameraMotion {
+            position: Track::new(keys.iter().map(|&(time, lookfrom, _)| Keyframe::new(time, lookfrom, interpolation)).collect()),
+            orientation: Track::new(
+                keys.iter()
+                    .map(|&(time, lookfrom, lookat)| Keyframe::new(time, orientation(lookfrom, lookat), interpolation))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// What autofocus brings into focus: a point in the scene, or the first
+// surface seen through image coordinates (s, t).
+#[derive(Clone, Copy)]
+pub enum Focus {
+    Point(Point3),
+    Image(f64, f64),
+}
+
+pub struct Stereo {
+    pub interocular: f64,
+    // Distance at which the eyes' views cross and objects show no parallax.
+    pub convergence: f64,
+}
+
+// Camera placement chosen by the scene; the image aspect ratio is only known
+// when the camera is built.
+pub struct CameraSettings {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vup: Vec3,
+    pub projection: Projection,
+    // Lens opening, cat's-eye vignetting and tilt-shift of perspective cameras.
+    pub aperture_shape: Aperture,
+    pub cat_eye: f64,
+    // Image window shift in units of the image size, and (tilt, swing) of the
+    // plane of focus in degrees.
+    pub lens_shift: (f64, f64),
+    pub focus_tilt: (f64, f64),
+    pub shutter: Shutter,
+    // Renders both eyes, top/bottom, instead of a single view.
+    pub stereo: Option<Stereo>,
+    // Keyframed pose used instead of lookfrom, lookat and vup.
+    pub motion: Option<CameraMotion>,
+    // Replaces the projection's focus distance when set; see autofocus.
+    pub focus: Option<Focus>,
+}
+
+impl CameraSettings {
+    // Sets the focus distance so that the focus target is sharp, measured
+    // along the view axis from lookfrom. Animated cameras focus at the pose
+    // of the moment the target is picked. Leaves the distance alone when the
+    // target cannot be found.
+    pub fn autofocus(&mut self, world: &HittableList, aspect_ratio: f64) {
+        let (target, time) = match self.focus {
+            None => return,
+            Some(Focus::Point(p)) => (p, self.shutter.open),
+            Some(Focus::Image(s, t)) => {
+                let hit = self.build(aspect_ratio)
+                    .central_ray(s, t)
+                    .and_then(|r| world.hit(&r, 0.001, f64::INFINITY).map(|rec| (rec.p, r.time())));
+                match hit {
+                    Some(hit) => hit,
+                    None => {
+                        eprintln!("Nothing to focus on at {}, {}", s, t);
+                        return;
+                    }
+                }
+            }
+        };
+
+        let (origin, forward) = match &self.motion {
+            Some(motion) => (
+                motion.position.at(time),
+                motion.orientation.at(time).rotate(&Vec3::new(0.0, 0.0, -1.0)),
+            ),
+            None => (self.lookfrom, Vec3::unit_vector(&(self.lookat - self.lookfrom))),
+        };
+        let distance = Vec3::dot(&(target - origin), &forward);
+        if distance <= 0.0 {
+            eprintln!("Focus target is behind the camera");
+            return;
+        }
+        match &mut self.projection {
+            Projection::Perspective { focus_dist, .. } | Projection::Realistic { focus_dist, .. } => {
+                *focus_dist = distance
+            }
+            _ => {}
+        }
+    }
+
+    pub fn build(&self, aspect_ratio: f64) -> Box<dyn Camera> {
+        let Some(motion) = &self.motion else {
+            return self.build_view(aspect_ratio, (self.lookfrom, self.lookat, self.vup));
+        };
+        // Built at the origin looking down -z, then carried along the tracks.
+        let pose = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0));
+        Box::new(AnimatedCamera::new(
+            self.build_view(aspect_ratio, pose),
+            motion.position.clone(),
+            motion.orientation.clone(),
+        ))
+    }
+
+    fn build_view(&self, aspect_ratio: f64, pose: (Point3, Point3, Vec3)) -> Box<dyn Camera> {
+        match &self.stereo {
+            None => self.build_eye(aspect_ratio, pose, 0.0, f64::INFINITY),
+            Some(stereo) => {
+                let offset = stereo.interocular / 2.0;
+                Box::new(StereoCamera::new(
+                    self.build_eye(2.0 * aspect_ratio, pose, -offset, stereo.convergence),
+                    self.build_eye(2.0 * aspect_ratio, pose, offset, stereo.convergence),
+                ))
+            }
+        }
+    }
+
+    // Camera for an eye moved offset along the image's horizontal axis.
+    fn build_eye(&self, aspect_ratio: f64, pose: (Point3, Point3, Vec3), offset: f64, convergence: f64) -> Box<dyn Camera> {
+        let ((lookfrom, lookat, vup), shutter) = (pose, self.shutter);
+        let w = Vec3::unit_vector(&(lookfrom - lookat));
+        let u = Vec3::unit_vector(&Vec3::cross(&vup, &w));
+        let eye = lookfrom + offset * u;
+        // Toed in towards the point where the eyes converge.
+        let target = if offset == 0.0 { lookat } else { lookfrom - convergence * w };
+
+        match &self.projection {
+            &Projection::Perspective { focal_length, f_number, focus_dist } => {
+                let vfov = 2.0 * (FILM_HEIGHT / 2.0 / focal_length).atan().to_degrees();
+                let aperture = MM * focal_length / f_number;
+                // Off-axis: parallel views whose image windows are shifted so
+                // that they coincide at the convergence distance.
+                let width = 2.0 * rtweekend::degrees_to_radians(vfov / 2.0).tan() * aspect_ratio;
+                Box::new(PerspectiveCamera::new(
+                    eye, lookat + offset * u, vup, vfov, aspect_ratio, aperture, focus_dist, shutter,
+                ).with_shift(-offset / (convergence * width), 0.0)
+                    .with_shift(self.lens_shift.0, self.lens_shift.1)
+                    .with_tilt(self.focus_tilt.0, self.focus_tilt.1)
+                    .with_aperture(self.aperture_shape.clone())
+                    .with_cat_eye(self.cat_eye))
+            }
+            &Projection::Orthographic { height } => Box::new(OrthographicCamera::new(
+                eye, target, vup, height, aspect_ratio, shutter,
+            )),
+            &Projection::Fisheye { fov, mapping } => Box::new(FisheyeCamera::new(
+                eye, target, vup, fov, mapping, aspect_ratio, shutter,
+            )),
+            Projection::Equirectangular => Box::new(
+                EquirectangularCamera::new(lookfrom, lookat, vup, shutter).with_eye(offset, convergence),
+            ),
+            Projection::Realistic { lens, film_diagonal, focus_dist } => Box::new(RealisticCamera::new(
+                eye, target, vup, lens, *film_diagonal, aspect_ratio, *focus_dist, shutter,
+            )),
+        }
+    }
+}
+
+pub struct Scene {
+    pub world: HittableList,
+    // Emissive objects, also present in world, that integrators sample directly.
+    pub lights: HittableList,
+    pub background: Background,
+    pub camera: CameraSettings,
+}
+
+impl Scene {
+    pub fn background(&self, r: &Ray) -> Color {
+        match self.background {
+            Background::Sky => {
+                let unit_direction = Vec3::unit_vector(&r.direction());
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+            }
+            Background::Solid(c) => c,
+        }
+    }
+}
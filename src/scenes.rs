@@ -0,0 +1,447 @@
+use std::rc::Rc;
+
+use crate::animation::{Interpolation, Keyframe, Quat, Track};
+use crate::aperture::Aperture;
+use crate::bump::{Bumped, Perturbation};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::layered::{Coated, Mix};
+use crate::masked::{AlphaMode, Masked};
+use crate::material::{
+    Conductor, Dielectric, DiffuseLight, Ior, Lambertian, Material, Metal, OrenNayar,
+    RoughDielectric,
+};
+use crate::moving_sphere::MovingSphere;
+use crate::principled::Principled;
+use crate::quad::Quad;
+use crate::rtweekend::{random, random_range};
+use crate::camera::Shutter;
+use crate::scene::{Background, CameraMotion, CameraSettings, Projection, Scene};
+use crate::sphere::Sphere;
+use crate::subsurface::Subsurface;
+use crate::texture::{AnimatedTexture, CheckerTexture, NoiseTexture, SolidColor};
+use crate::thin_film::ThinFilm;
+use crate::transform::AnimatedTransform;
+use crate::triangle::Triangle;
+use crate::vec3::{Color, Point3, Vec3};
+
+fn default_camera() -> CameraSettings {
+    CameraSettings {
+        lookfrom: Point3::new(13.0, 2.0, 3.0),
+        lookat: Point3::new(0.0, 0.0, 0.0),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        // About 20 degrees vertically, with a lens opening 0.1 across.
+        projection: Projection::Perspective {
+            focal_length: 68.0,
+            f_number: 6.8,
+            focus_dist: 10.0,
+        },
+        aperture_shape: Aperture::Circle,
+        cat_eye: 0.0,
+        lens_shift: (0.0, 0.0),
+        focus_tilt: (0.0, 0.0),
+        shutter: Shutter::new(0.0, 1.0),
+        stereo: None,
+        motion: None,
+        focus: None,
+    }
+}
+
+fn random_world(glass: Rc<dyn Material>, center_glass: Rc<dyn Material>) -> HittableList {
+    let mut world = HittableList::new();
+
+    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = random();
+            let center = Point3::new(a as f64 + 0.9 * random(), 0.2, b as f64 + 0.9 * random());
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let object: Box<dyn Hittable> = if choose_mat < 0.8 {
+                    let albedo = Color::random() * Color::random();
+                    let center2 = center + Vec3::new(0.0, random_range(0.0, 0.5), 0.0);
+                    let mat = Rc::new(Lambertian::new(albedo));
+
+                    Box::new(MovingSphere::new(center, center2, 0.2, 0.0, 1.0, mat))
+                } else if choose_mat < 0.95 {
+                    let albedo = Color::random_range(0.5, 1.0);
+                    let fuzz = random_range(0.0, 0.5);
+                    let mat = Rc::new(Metal::new(albedo, fuzz));
+                    Box::new(Sphere::new(center, 0.2, mat))
+                } else {
+                    Box::new(Sphere::new(center, 0.2, glass.clone()))
+                };
+                world.add(object);
+            }
+        }
+    }
+
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        center_glass,
+    )));
+    let material2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    world.add(Box::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        material2,
+    )));
+    let material3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    world.add(Box::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+
+    world
+}
+
+pub fn random_scene() -> Scene {
+    Scene {
+        world: random_world(
+            Rc::new(Dielectric::new(1.5)),
+            Rc::new(Dielectric::new(1.5)),
+        ),
+        lights: HittableList::new(),
+        background: Background::Sky,
+        camera: default_camera(),
+    }
+}
+
+// The random scene under a single overhead light and a black sky, so that the
+// glass spheres cast caustics. The glass is dispersive (crown glass for the
+// small spheres, dense flint for the large one) for use with --spectral.
+pub fn night_scene() -> Scene {
+    let crown = Rc::new(Dielectric::dispersive(Ior::Cauchy { a: 1.5046, b: 0.00420 }));
+    let flint = Rc::new(Dielectric::dispersive(Ior::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    }));
+    let mut world = random_world(crown, flint);
+    let mut lights = HittableList::new();
+
+    let light = Rc::new(DiffuseLight::new(Color::new(20.0, 20.0, 20.0)));
+    let center = Point3::new(2.0, 8.0, 2.0);
+    world.add(Box::new(Sphere::new(center, 1.5, light.clone())));
+    lights.add(Box::new(Sphere::new(center, 1.5, light)));
+
+    Scene {
+        world,
+        lights,
+        background: Background::Solid(Color::new(0.0, 0.0, 0.0)),
+        camera: default_camera(),
+    }
+}
+
+// A row of spheres on a grey ground plane, one per material, lined up across
+// the default view for side by side comparisons.
+pub fn materials_scene() -> Scene {
+    let mut world = HittableList::new();
+
+    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    let gold_eta = Color::new(0.143, 0.374, 1.442);
+    let gold_k = Color::new(3.983, 2.385, 1.603);
+    let copper_eta = Color::new(0.200, 0.924, 1.102);
+    let copper_k = Color::new(3.912, 2.452, 2.142);
+    let aluminium_eta = Color::new(1.657, 0.880, 0.521);
+    let aluminium_k = Color::new(9.224, 6.270, 4.837);
+
+    let mut plastic = Principled::new(Rc::new(SolidColor::new(Color::new(0.7, 0.1, 0.1))));
+    plastic.roughness = Rc::new(SolidColor::gray(0.4));
+    plastic.sheen = Rc::new(SolidColor::gray(0.5));
+    plastic.clearcoat = Rc::new(SolidColor::gray(1.0));
+    let mut checkered_metal = Principled::new(Rc::new(SolidColor::new(Color::new(0.9, 0.6, 0.3))));
+    checkered_metal.metallic = Rc::new(SolidColor::gray(1.0));
+    checkered_metal.roughness = Rc::new(CheckerTexture::new(
+        8.0,
+        Rc::new(SolidColor::gray(0.1)),
+        Rc::new(SolidColor::gray(0.5)),
+    ));
+    let mut tinted_glass = Principled::new(Rc::new(SolidColor::new(Color::new(0.6, 0.9, 0.7))));
+    tinted_glass.transmission = Rc::new(SolidColor::gray(1.0));
+    tinted_glass.roughness = Rc::new(SolidColor::gray(0.15));
+
+    let rusty = Mix::new(
+        Rc::new(Conductor::new(aluminium_eta, aluminium_k, 0.1, 0.1)),
+        Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))),
+        Rc::new(CheckerTexture::new(
+            10.0,
+            Rc::new(SolidColor::gray(0.2)),
+            Rc::new(SolidColor::gray(0.8)),
+        )),
+    );
+
+    let materials: Vec<Rc<dyn Material>> = vec![
+        Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.3)),
+        Rc::new(OrenNayar::new(Color::new(0.7, 0.45, 0.3), 30.0)),
+        Rc::new(Bumped::new(
+            Rc::new(Lambertian::new(Color::new(0.6, 0.6, 0.55))),
+            Perturbation::Bump {
+                height: Rc::new(NoiseTexture::new(20.0)),
+                scale: 0.05,
+            },
+        )),
+        Rc::new(Bumped::new(
+            Rc::new(Conductor::new(copper_eta, copper_k, 0.05, 0.05)),
+            Perturbation::NormalMap(Rc::new(CheckerTexture::new(
+                12.0,
+                Rc::new(SolidColor::new(Color::new(0.65, 0.5, 0.98))),
+                Rc::new(SolidColor::new(Color::new(0.35, 0.5, 0.98))),
+            ))),
+        )),
+        Rc::new(Subsurface::new(
+            Color::new(0.99, 0.95, 0.85),
+            Color::new(0.15, 0.1, 0.08),
+            1.4,
+            0.3,
+        )),
+        Rc::new(Conductor::new(aluminium_eta, aluminium_k, 0.0, 0.0)),
+        Rc::new(Conductor::new(copper_eta, copper_k, 0.3, 0.3)),
+        Rc::new(Conductor::new(gold_eta, gold_k, 0.05, 0.4)),
+        Rc::new(Dielectric::new(1.5)),
+        Rc::new(RoughDielectric::new(1.5, 0.0, 0.0)),
+        Rc::new(RoughDielectric::new(1.5, 0.2, 0.2)),
+        Rc::new(Dielectric::new(1.5).with_absorption(Color::new(0.1, 1.0, 2.0))),
+        Rc::new(RoughDielectric::new(1.5, 0.1, 0.1).with_absorption(Color::new(2.0, 0.6, 0.1))),
+        Rc::new(plastic),
+        Rc::new(checkered_metal),
+        Rc::new(tinted_glass),
+        Rc::new(rusty),
+        Rc::new(Coated::new(Rc::new(Lambertian::new(Color::new(0.1, 0.3, 0.7))), 1.5)),
+        Rc::new(Coated::new(Rc::new(Conductor::new(copper_eta, copper_k, 0.3, 0.3)), 1.5)),
+        Rc::new(Dielectric::new(1.0).with_thin_film(ThinFilm::new(Rc::new(SolidColor::gray(380.0)), 1.33))),
+        Rc::new(
+            Conductor::new(aluminium_eta, aluminium_k, 0.05, 0.05).with_thin_film(ThinFilm::new(
+                Rc::new(CheckerTexture::new(
+                    6.0,
+                    Rc::new(SolidColor::gray(250.0)),
+                    Rc::new(SolidColor::gray(450.0)),
+                )),
+                1.6,
+            )),
+        ),
+    ];
+
+    // Rows of up to six spheres perpendicular to the default view direction,
+    // later rows further back and staggered so they show between the spheres
+    // in front. The last two slots hold cut out spheres.
+    let across = Vec3::unit_vector(&Vec3::new(3.0, 0.0, -13.0));
+    let back = Vec3::unit_vector(&Vec3::new(-13.0, 0.0, -3.0));
+    let per_row = 6;
+    let n = materials.len() + 2;
+    let center = |i: usize| {
+        let row = i / per_row;
+        let in_row = per_row.min(n - row * per_row);
+        let stagger = (row % 3) as f64 / 3.0;
+        let offset = ((i % per_row) as f64 - (in_row - 1) as f64 / 2.0 + stagger) * 1.2;
+        Point3::new(0.0, 0.4, 0.0) + offset * across + (row as f64 * 1.5) * back
+    };
+    for (i, mat) in materials.into_iter().enumerate() {
+        world.add(Box::new(Sphere::new(center(i), 0.4, mat)));
+    }
+
+    let cage = Sphere::new(
+        center(n - 2),
+        0.4,
+        Rc::new(Lambertian::new(Color::new(0.8, 0.3, 0.1))),
+    );
+    world.add(Box::new(Masked::new(
+        Box::new(cage),
+        Rc::new(CheckerTexture::new(
+            10.0,
+            Rc::new(SolidColor::gray(0.0)),
+            Rc::new(SolidColor::gray(1.0)),
+        )),
+        AlphaMode::Threshold(0.5),
+    )));
+    let veil = Sphere::new(
+        center(n - 1),
+        0.4,
+        Rc::new(Lambertian::new(Color::new(0.9, 0.9, 0.9))),
+    );
+    world.add(Box::new(Masked::new(
+        Box::new(veil),
+        Rc::new(NoiseTexture::new(4.0)),
+        AlphaMode::Stochastic,
+    )));
+
+    Scene {
+        world,
+        lights: HittableList::new(),
+        background: Background::Sky,
+        camera: default_camera(),
+    }
+}
+
+// Keyframed motion over two seconds: a tumbling dumbbell sliding across the
+// frame, a pulsing glass sphere, a sphere changing color and roughness, and
+// a camera swinging around them. The shutter is open for half of a 24 fps
+// frame starting at time 0.
+pub fn animated_scene() -> Scene {
+    let mut world = HittableList::new();
+
+    let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        ground_material,
+    )));
+
+    let mut dumbbell = HittableList::new();
+    dumbbell.add(Box::new(Sphere::new(
+        Point3::new(-0.6, 0.0, 0.0),
+        0.35,
+        Rc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 0.05)),
+    )));
+    dumbbell.add(Box::new(Sphere::new(
+        Point3::new(0.6, 0.0, 0.0),
+        0.35,
+        Rc::new(Lambertian::new(Color::new(0.1, 0.3, 0.8))),
+    )));
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    world.add(Box::new(AnimatedTransform::new(
+        Box::new(dumbbell),
+        Track::new(vec![
+            Keyframe::new(0.0, Vec3::new(0.5, 0.5, -3.0), Interpolation::EASE_IN_OUT),
+            Keyframe::new(2.0, Vec3::new(0.5, 0.5, 3.0), Interpolation::Linear),
+        ]),
+        Track::new(
+            (0..=3)
+                .map(|k| Keyframe::new(k as f64 * 2.0 / 3.0, Quat::from_axis_angle(&up, 120.0 * k as f64), Interpolation::Linear))
+                .collect(),
+        ),
+        Track::constant(1.0),
+    )));
+
+    world.add(Box::new(AnimatedTransform::new(
+        Box::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Rc::new(Dielectric::new(1.5)))),
+        Track::constant(Vec3::new(-2.0, 1.0, 0.0)),
+        Track::constant(Quat::identity()),
+        Track::new(vec![
+            Keyframe::new(0.0, 0.6, Interpolation::EASE_IN_OUT),
+            Keyframe::new(1.0, 1.0, Interpolation::EASE_IN_OUT),
+            Keyframe::new(2.0, 0.6, Interpolation::Linear),
+        ]),
+    )));
+
+    let mut chameleon = Principled::new(Rc::new(AnimatedTexture::new(Track::new(vec![
+        Keyframe::new(0.0, Color::new(0.8, 0.1, 0.1), Interpolation::Linear),
+        Keyframe::new(1.0, Color::new(0.1, 0.1, 0.8), Interpolation::Step),
+        Keyframe::new(1.5, Color::new(0.1, 0.7, 0.1), Interpolation::Linear),
+    ]))));
+    chameleon.metallic = Rc::new(SolidColor::gray(1.0));
+    chameleon.roughness = Rc::new(AnimatedTexture::new(Track::new(vec![
+        Keyframe::new(0.0, Color::new(0.05, 0.05, 0.05), Interpolation::Linear),
+        Keyframe::new(2.0, Color::new(0.6, 0.6, 0.6), Interpolation::Linear),
+    ])));
+    world.add(Box::new(Sphere::new(Point3::new(2.5, 1.0, 0.0), 1.0, Rc::new(chameleon))));
+
+    let mut camera = default_camera();
+    camera.shutter = Shutter::new(0.0, 1.0 / 48.0);
+    camera.motion = Some(CameraMotion::look_at(
+        &[
+            (0.0, Point3::new(13.0, 2.0, 3.0), Point3::new(0.0, 0.5, 0.0)),
+            (1.0, Point3::new(9.0, 5.0, -9.0), Point3::new(0.0, 0.5, 0.0)),
+            (2.0, Point3::new(-3.0, 3.0, -12.0), Point3::new(0.0, 0.5, 0.0)),
+        ],
+        up,
+        Interpolation::EASE_IN_OUT,
+    ));
+
+    Scene {
+        world,
+        lights: HittableList::new(),
+        background: Background::Sky,
+        camera,
+    }
+}
+
+// A box of quads, open towards the camera and lit by a quad in the ceiling,
+// with a normal mapped back wall, a bump mapped floor and a bump mapped
+// pyramid of triangles.
+pub fn shapes_scene() -> Scene {
+    let mut world = HittableList::new();
+    let mut lights = HittableList::new();
+
+    let white = Rc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let floor = Rc::new(Bumped::new(
+        Rc::new(Lambertian::new(Color::new(0.6, 0.55, 0.5))),
+        Perturbation::Bump {
+            height: Rc::new(NoiseTexture::new(4.0)),
+            scale: 0.02,
+        },
+    ));
+    let tiles = Rc::new(Bumped::new(
+        white.clone(),
+        Perturbation::NormalMap(Rc::new(CheckerTexture::new(
+            8.0,
+            Rc::new(SolidColor::new(Color::new(0.65, 0.5, 0.98))),
+            Rc::new(SolidColor::new(Color::new(0.35, 0.5, 0.98))),
+        ))),
+    ));
+    let red = Rc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let green = Rc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+
+    let (x, y, z) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+    world.add(Box::new(Quad::new(Point3::new(-3.0, 0.0, 3.0), 6.0 * x, -6.0 * z, floor)));
+    world.add(Box::new(Quad::new(Point3::new(-3.0, 0.0, -3.0), 6.0 * x, 5.0 * y, tiles)));
+    world.add(Box::new(Quad::new(Point3::new(-3.0, 0.0, 3.0), -6.0 * z, 5.0 * y, red)));
+    world.add(Box::new(Quad::new(Point3::new(3.0, 0.0, -3.0), 6.0 * z, 5.0 * y, green)));
+    world.add(Box::new(Quad::new(Point3::new(-3.0, 5.0, -3.0), 6.0 * x, 6.0 * z, white)));
+
+    let light = Rc::new(DiffuseLight::new(Color::new(15.0, 15.0, 15.0)));
+    let (corner, u, v) = (Point3::new(-1.0, 4.99, -1.0), 2.0 * x, 2.0 * z);
+    world.add(Box::new(Quad::new(corner, u, v, light.clone())));
+    lights.add(Box::new(Quad::new(corner, u, v, light)));
+
+    let stone = Rc::new(Bumped::new(
+        Rc::new(Metal::new(Color::new(0.8, 0.7, 0.5), 0.3)),
+        Perturbation::Bump {
+            height: Rc::new(NoiseTexture::new(6.0)),
+            scale: 0.05,
+        },
+    ));
+    let apex = Point3::new(0.8, 2.2, 0.0);
+    let base = [
+        Point3::new(-0.4, 0.0, 1.2),
+        Point3::new(2.0, 0.0, 1.2),
+        Point3::new(2.0, 0.0, -1.2),
+        Point3::new(-0.4, 0.0, -1.2),
+    ];
+    for i in 0..4 {
+        world.add(Box::new(
+            Triangle::new(base[i], base[(i + 1) % 4], apex, stone.clone())
+                .with_uvs([(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)]),
+        ));
+    }
+    world.add(Box::new(Sphere::new(Point3::new(-1.7, 0.8, 1.0), 0.8, Rc::new(Dielectric::new(1.5)))));
+
+    let mut camera = default_camera();
+    camera.lookfrom = Point3::new(0.0, 2.5, 13.0);
+    camera.lookat = Point3::new(0.0, 2.3, 0.0);
+    camera.projection = Projection::Perspective {
+        focal_length: 55.0,
+        f_number: 16.0,
+        focus_dist: 13.0,
+    };
+
+    Scene {
+        world,
+        lights,
+        background: Background::Solid(Color::new(0.0, 0.0, 0.0)),
+        camera,
+    }
+}
@@ -0,0 +1,170 @@
+use std::sync::OnceLock;
+
+use crate::ray::Ray;
+use crate::vec3::{Color, Vec3};
+
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 720.0;
+
+// Reference wavelength (sodium D line) at which dispersive media are
+// evaluated when rendering in RGB.
+pub const LAMBDA_D: f64 = 589.3;
+
+// Smits' basis spectra for RGB to reflectance conversion, sampled at ten
+// evenly spaced wavelengths from LAMBDA_MIN to LAMBDA_MAX.
+const SMITS_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+fn basis(spectrum: &[f64; 10], lambda: f64) -> f64 {
+    let x = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * 9.0;
+    if x <= 0.0 {
+        return spectrum[0];
+    }
+    if x >= 9.0 {
+        return spectrum[9];
+    }
+    let i = x as usize;
+    let f = x - i as f64;
+    (1.0 - f) * spectrum[i] + f * spectrum[i + 1]
+}
+
+// Value at lambda of a smooth spectrum whose RGB appearance is rgb.
+pub fn upsample(rgb: Color, lambda: f64) -> f64 {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    if r <= g && r <= b {
+        r * basis(&SMITS_WHITE, lambda)
+            + if g <= b {
+                (g - r) * basis(&SMITS_CYAN, lambda) + (b - g) * basis(&SMITS_BLUE, lambda)
+            } else {
+                (b - r) * basis(&SMITS_CYAN, lambda) + (g - b) * basis(&SMITS_GREEN, lambda)
+            }
+    } else if g <= r && g <= b {
+        g * basis(&SMITS_WHITE, lambda)
+            + if r <= b {
+                (r - g) * basis(&SMITS_MAGENTA, lambda) + (b - r) * basis(&SMITS_BLUE, lambda)
+            } else {
+                (b - g) * basis(&SMITS_MAGENTA, lambda) + (r - b) * basis(&SMITS_RED, lambda)
+            }
+    } else {
+        b * basis(&SMITS_WHITE, lambda)
+            + if r <= g {
+                (r - b) * basis(&SMITS_YELLOW, lambda) + (g - r) * basis(&SMITS_GREEN, lambda)
+            } else {
+                (g - b) * basis(&SMITS_YELLOW, lambda) + (r - g) * basis(&SMITS_RED, lambda)
+            }
+    }
+}
+
+// Converts rgb into the space the ray carries light in: unchanged for RGB
+// rays, or the upsampled spectrum at its wavelengths for spectral rays.
+pub fn sample_rgb(rgb: Color, r: &Ray) -> Color {
+    sample_rgb_at(rgb, r.wavelengths.as_ref())
+}
+
+pub fn sample_rgb_at(rgb: Color, wavelengths: Option<&Wavelengths>) -> Color {
+    match wavelengths {
+        Some(w) => Color::new(
+            upsample(rgb, w.lambda[0]),
+            upsample(rgb, w.lambda[1]),
+            upsample(rgb, w.lambda[2]),
+        ),
+        None => rgb,
+    }
+}
+
+fn lobe(lambda: f64, mu: f64, sigma1: f64, sigma2: f64) -> f64 {
+    let t = (lambda - mu) / if lambda < mu { sigma1 } else { sigma2 };
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 colour matching functions, multi-lobe fit by Wyman et al.
+fn cie_xyz(lambda: f64) -> Vec3 {
+    Vec3::new(
+        1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * lobe(lambda, 501.1, 20.4, 26.2),
+        0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1),
+        1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+fn xyz_to_linear_srgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+// RGB of the constant unit spectrum, used to white balance film output so
+// that white albedos and lights stay white in spectral mode.
+fn white_rgb() -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+        let mut lambda = LAMBDA_MIN + 0.5;
+        while lambda < LAMBDA_MAX {
+            xyz += cie_xyz(lambda);
+            lambda += 1.0;
+        }
+        xyz_to_linear_srgb(xyz)
+    })
+}
+
+// Hero wavelength sampling: a uniformly chosen hero wavelength plus two
+// companions rotated by a third of the range, each carried in one component of
+// a Color. Dispersive events keep only the hero.
+#[derive(Clone, Copy)]
+pub struct Wavelengths {
+    pub lambda: [f64; 3],
+    pub secondary_terminated: bool,
+}
+
+impl Wavelengths {
+    pub fn sample(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = LAMBDA_MIN + u * range;
+        let mut lambda = [hero; 3];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            *l = hero + i as f64 * range / 3.0;
+            if *l > LAMBDA_MAX {
+                *l -= range;
+            }
+        }
+        Wavelengths {
+            lambda,
+            secondary_terminated: false,
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    // Drops the companion wavelengths, returning the factor by which the
+    // path throughput must be scaled so the hero alone stays unbiased.
+    pub fn terminate_secondary(&mut self) -> Color {
+        if self.secondary_terminated {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            self.secondary_terminated = true;
+            Color::new(3.0, 0.0, 0.0)
+        }
+    }
+
+    // Linear sRGB of radiance l carried at these wavelengths.
+    pub fn to_rgb(self, l: Color) -> Color {
+        let pdf = 1.0 / (LAMBDA_MAX - LAMBDA_MIN);
+        let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            xyz += cie_xyz(self.lambda[i]) * (l[i] / (3.0 * pdf));
+        }
+        let white = white_rgb();
+        let rgb = xyz_to_linear_srgb(xyz);
+        Color::new(rgb.x() / white.x(), rgb.y() / white.y(), rgb.z() / white.z())
+    }
+}
@@ -3,6 +3,7 @@ use std::rc::Rc;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::rtweekend::PI;
 use crate::vec3::{Point3, Vec3};
 
 pub struct Sphere {
@@ -19,6 +20,27 @@ impl Sphere {
             mat: m,
         }
     }
+
+    // Maps a point on the unit sphere to (u, v) in [0, 1]^2: u is the angle
+    // around the y axis starting from -x, v runs from the bottom pole up.
+    pub fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // Derivatives of the point at unit normal n on a sphere of the given
+    // radius with respect to the (u, v) of get_sphere_uv. At the poles, where
+    // they vanish, any tangent frame will do.
+    pub fn get_sphere_tangents(n: &Vec3, radius: f64) -> (Vec3, Vec3) {
+        let sin_theta = (n.x() * n.x() + n.z() * n.z()).sqrt();
+        if sin_theta < 1.0e-6 {
+            return (Vec3::new(0.0, 0.0, -radius), Vec3::new(PI * radius, 0.0, 0.0));
+        }
+        let dpdu = 2.0 * PI * radius * Vec3::new(n.z(), 0.0, -n.x());
+        let dpdv = PI * radius * Vec3::new(-n.x() * n.y() / sin_theta, sin_theta, -n.y() * n.z() / sin_theta);
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
@@ -48,16 +70,49 @@ impl Hittable for Sphere {
 
         let p = r.at(root);
         let outward_normal = (p - self.center) / self.radius;
+        let (u, v) = Self::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Self::get_sphere_tangents(&outward_normal, self.radius);
 
         let mut rec = HitRecord {
-            p: p,
+            p,
             normal: outward_normal,
             mat: self.mat.clone(),
             t: root,
+            time: r.time(),
+            u,
+            v,
+            dpdu,
+            dpdv,
             front_face: false,
         };
-        rec.set_face_normal(&r, &outward_normal);
+        rec.set_face_normal(r, &outward_normal);
 
         Some(rec)
     }
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let outward_normal = Vec3::random_unit_vector();
+        let (u, v) = Self::get_sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = Self::get_sphere_tangents(&outward_normal, self.radius);
+        let rec = HitRecord {
+            p: self.center + self.radius * outward_normal,
+            normal: outward_normal,
+            mat: self.mat.clone(),
+            t: 0.0,
+            time,
+            u,
+            v,
+            dpdu,
+            dpdv,
+            front_face: true,
+        };
+        Some((rec, 1.0 / (4.0 * PI * self.radius * self.radius)))
+    }
+
+    fn surface_pdf(&self, p: &Point3, _time: f64) -> f64 {
+        if ((*p - self.center).length() - self.radius).abs() < 1.0e-6 * self.radius {
+            1.0 / (4.0 * PI * self.radius * self.radius)
+        } else {
+            0.0
+        }
+    }
 }
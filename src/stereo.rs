@@ -0,0 +1,72 @@
+use crate::camera::{Camera, Shutter};
+use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::vec3::{Color, Point3};
+
+// Both eyes in one image, the left eye in the top half and the right eye in
+// the bottom half. Each eye is a full camera with half the image height.
+pub struct StereoCamera {
+    left: Box<dyn Camera>,
+    right: Box<dyn Camera>,
+}
+
+impl StereoCamera {
+    pub fn new(left: Box<dyn Camera>, right: Box<dyn Camera>) -> Self {
+        StereoCamera { left, right }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        if t >= 0.5 {
+            self.left.get_ray(s, 2.0 * t - 1.0)
+        } else {
+            self.right.get_ray(s, 2.0 * t)
+        }
+    }
+
+    fn shutter(&self) -> &Shutter {
+        self.left.shutter()
+    }
+
+    fn central_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        if t >= 0.5 {
+            self.left.central_ray(s, 2.0 * t - 1.0)
+        } else {
+            self.right.central_ray(s, 2.0 * t)
+        }
+    }
+
+    fn has_importance(&self) -> bool {
+        self.left.has_importance() && self.right.has_importance()
+    }
+
+    // A ray belongs to the eye whose lens it leaves. Each eye covers half of
+    // the image, so its importance is halved.
+    fn we(&self, r: &Ray) -> Option<(Color, f64, f64)> {
+        match self.left.we(r) {
+            Some((we, s, t)) => Some((0.5 * we, s, 0.5 + 0.5 * t)),
+            None => self.right.we(r).map(|(we, s, t)| (0.5 * we, s, 0.5 * t)),
+        }
+    }
+
+    // get_ray picks the eye from t, so each lens is sampled half as often.
+    fn pdf_we(&self, r: &Ray) -> (f64, f64) {
+        let (pdf_pos, pdf_dir) = match self.left.pdf_we(r) {
+            (pdf_pos, _) if pdf_pos <= 0.0 => self.right.pdf_we(r),
+            pdf => pdf,
+        };
+        (0.5 * pdf_pos, pdf_dir)
+    }
+
+    // Connects to either eye with equal probability.
+    fn sample_wi(&self, p: &Point3, time: f64) -> Option<(Ray, f64, Color, f64, f64)> {
+        if random() < 0.5 {
+            let (r, pdf, we, s, t) = self.left.sample_wi(p, time)?;
+            Some((r, 0.5 * pdf, 0.5 * we, s, 0.5 + 0.5 * t))
+        } else {
+            let (r, pdf, we, s, t) = self.right.sample_wi(p, time)?;
+            Some((r, 0.5 * pdf, 0.5 * we, s, 0.5 * t))
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::microfacet;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::{random, PI};
+use crate::spectrum;
+use crate::vec3::{Color, Vec3};
+
+// Translucent material such as wax, skin or marble: a smooth dielectric
+// boundary around a scattering medium that light random walks through until
+// it leaves the object again. The object must be closed.
+pub struct Subsurface {
+    // Fraction of the light surviving each scattering event.
+    albedo: Color,
+    // Extinction coefficient, the inverse of the mean free path.
+    sigma_t: Color,
+    ir: f64,
+    // Henyey-Greenstein asymmetry, from -1 (backward) to 1 (forward).
+    g: f64,
+}
+
+impl Subsurface {
+    pub fn new(albedo: Color, mean_free_path: Color, ir: f64, g: f64) -> Self {
+        Subsurface {
+            albedo,
+            sigma_t: Color::new(
+                1.0 / mean_free_path.x(),
+                1.0 / mean_free_path.y(),
+                1.0 / mean_free_path.z(),
+            ),
+            ir,
+            g,
+        }
+    }
+
+    // Samples a new direction for light travelling along dir.
+    fn sample_phase(&self, dir: &Vec3) -> Vec3 {
+        let u = random();
+        let cos_theta = if self.g.abs() < 1.0e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - self.g * self.g) / (1.0 - self.g + 2.0 * self.g * u);
+            (1.0 + self.g * self.g - s * s) / (2.0 * self.g)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random();
+        Onb::from_w(dir).to_world(&Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+    }
+}
+
+fn exp(c: Color) -> Color {
+    Color::new(c.x().exp(), c.y().exp(), c.z().exp())
+}
+
+fn average(c: Color) -> f64 {
+    (c.x() + c.y() + c.z()) / 3.0
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let eta = if rec.front_face { self.ir } else { 1.0 / self.ir };
+        let unit_direction = Vec3::unit_vector(&r_in.direction());
+        let cos_theta = Vec3::dot(&-unit_direction, &rec.normal).min(1.0);
+
+        let direction = if random() < microfacet::fresnel_dielectric(cos_theta, eta) {
+            Vec3::reflect(&unit_direction, &rec.normal)
+        } else {
+            Vec3::refract(&unit_direction, &rec.normal, 1.0 / eta)
+        };
+        let scattered = Ray {
+            orig: rec.p,
+            dir: direction,
+            tm: r_in.time(),
+            wavelengths: r_in.wavelengths,
+        };
+        Some((scattered, Color::new(1.0, 1.0, 1.0)))
+    }
+
+    // Free flight distances are sampled from one channel picked at random and
+    // weighted by the average density over all three.
+    fn sample_interior(&self, r: &Ray, rec: &HitRecord) -> (Color, Option<Ray>) {
+        if rec.front_face {
+            return (Color::new(1.0, 1.0, 1.0), None);
+        }
+        let sigma_t = spectrum::sample_rgb(self.sigma_t, r);
+        let albedo = spectrum::sample_rgb(self.albedo, r);
+        let length = r.direction().length();
+        let dist_max = rec.t * length;
+
+        let channel = ((3.0 * random()) as usize).min(2);
+        let t = -(1.0 - random()).ln() / sigma_t[channel];
+        if t >= dist_max {
+            let tr = exp(-dist_max * sigma_t);
+            return (tr / average(tr), None);
+        }
+
+        let tr = exp(-t * sigma_t);
+        let pdf = average(sigma_t * tr);
+        let scattered = Ray {
+            orig: r.at(t / length),
+            dir: self.sample_phase(&(r.direction() / length)),
+            tm: r.time(),
+            wavelengths: r.wavelengths,
+        };
+        (albedo * sigma_t * tr / pdf, Some(scattered))
+    }
+}
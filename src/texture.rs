@@ -0,0 +1,181 @@
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+use crate::animation::Track;
+use crate::perlin::Perlin;
+use crate::vec3::{Color, Point3};
+
+pub trait Texture {
+    // Value at surface coordinates (u, v) and point p, at the given time.
+    fn value(&self, u: f64, v: f64, p: &Point3, time: f64) -> Color;
+
+    // Scalar material parameters read the average of the channels, so grey
+    // textures give the value one would expect.
+    fn scalar(&self, u: f64, v: f64, p: &Point3, time: f64) -> f64 {
+        let c = self.value(u, v, p, time);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+}
+
+pub struct SolidColor {
+    color_value: Color,
+}
+
+impl SolidColor {
+    pub fn new(c: Color) -> Self {
+        SolidColor { color_value: c }
+    }
+
+    pub fn gray(value: f64) -> Self {
+        Self::new(Color::new(value, value, value))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3, _time: f64) -> Color {
+        self.color_value
+    }
+}
+
+// Solid 3D checkerboard alternating between two textures, with cells of size
+// 1 / scale.
+pub struct CheckerTexture {
+    scale: f64,
+    even: Rc<dyn Texture>,
+    odd: Rc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Rc<dyn Texture>, odd: Rc<dyn Texture>) -> Self {
+        CheckerTexture { scale, even, odd }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3, time: f64) -> Color {
+        let cells = (self.scale * p.x()).floor() + (self.scale * p.y()).floor() + (self.scale * p.z()).floor();
+        if cells as i64 % 2 == 0 {
+            self.even.value(u, v, p, time)
+        } else {
+            self.odd.value(u, v, p, time)
+        }
+    }
+}
+
+// Color keyframed over time, for animating material parameters.
+pub struct AnimatedTexture {
+    track: Track<Color>,
+}
+
+impl AnimatedTexture {
+    pub fn new(track: Track<Color>) -> Self {
+        AnimatedTexture { track }
+    }
+}
+
+impl Texture for AnimatedTexture {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3, time: f64) -> Color {
+        self.track.at(time)
+    }
+}
+
+// Marble-like pattern of turbulent Perlin noise; scale sets the frequency.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64) -> Self {
+        NoiseTexture {
+            noise: Perlin::new(),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3, _time: f64) -> Color {
+        Color::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + (self.scale * p.z() + 10.0 * self.noise.turb(p, 7)).sin())
+    }
+}
+
+// Bitmap loaded from a PPM file (ASCII P3 or binary P6), looked up with the
+// nearest pixel. Values are decoded with the same gamma 2 the film writes.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn from_ppm(path: &str) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, msg));
+
+        // Header: magic, width, height and maxval, separated by whitespace and
+        // comments running to the end of the line.
+        let mut pos = 0;
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+                if data[pos] == b'#' {
+                    while pos < data.len() && data[pos] != b'\n' {
+                        pos += 1;
+                    }
+                } else {
+                    pos += 1;
+                }
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid("truncated header"));
+            }
+            header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+        }
+        let number = |s: &str| s.parse::<usize>().map_err(|_| invalid("bad header"));
+        let (width, height, maxval) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+        if maxval == 0 || maxval > 255 {
+            return Err(invalid("unsupported maxval"));
+        }
+
+        let samples: Vec<usize> = match header[0].as_str() {
+            "P3" => String::from_utf8_lossy(&data[pos..])
+                .split_ascii_whitespace()
+                .map(number)
+                .collect::<io::Result<_>>()?,
+            "P6" => data[(pos + 1).min(data.len())..].iter().map(|&b| b as usize).collect(),
+            _ => return Err(invalid("not a P3 or P6 file")),
+        };
+        if samples.len() < 3 * width * height {
+            return Err(invalid("truncated pixel data"));
+        }
+
+        let decode = |s: usize| {
+            let c = s as f64 / maxval as f64;
+            c * c
+        };
+        let pixels = samples
+            .chunks(3)
+            .take(width * height)
+            .map(|c| Color::new(decode(c[0]), decode(c[1]), decode(c[2])))
+            .collect();
+        Ok(ImageTexture { width, height, pixels })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3, _time: f64) -> Color {
+        if self.pixels.is_empty() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let i = ((u.clamp(0.0, 1.0) * self.width as f64) as usize).min(self.width - 1);
+        // Rows are stored top to bottom.
+        let j = (((1.0 - v.clamp(0.0, 1.0)) * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i]
+    }
+}
@@ -0,0 +1,154 @@
+use std::ops::{Add, Div, Mul, Sub};
+use std::rc::Rc;
+
+use crate::hittable::HitRecord;
+use crate::rtweekend::PI;
+use crate::spectrum::{self, Wavelengths};
+use crate::texture::Texture;
+use crate::vec3::Color;
+
+// Wavelengths in nm standing in for the red, green and blue channels when
+// rendering in RGB.
+const RGB_WAVELENGTHS: [f64; 3] = [630.0, 532.0, 465.0];
+
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+
+    fn norm_squared(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root.
+    fn sqrt(self) -> Self {
+        let n = self.norm_squared().sqrt();
+        let re = (0.5 * (n + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (n - self.re)).max(0.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    // e^(i z)
+    fn exp_i(z: Self) -> Self {
+        let m = (-z.im).exp();
+        Self::new(m * z.re.cos(), m * z.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, o: Self) -> Self {
+        Self::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, o: Self) -> Self {
+        Self::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, o: Self) -> Self {
+        Self::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, o: Self) -> Self {
+        let d = o.norm_squared();
+        Self::new(
+            (self.re * o.re + self.im * o.im) / d,
+            (self.im * o.re - self.re * o.im) / d,
+        )
+    }
+}
+
+// Reflectance of a film of index n2 and thickness d (nm) between a medium of
+// index n1 and a substrate of complex index n3, summing the waves reflected
+// back and forth inside the film (Airy).
+fn airy_reflectance(cos_i: f64, lambda: f64, d: f64, n1: f64, n2: f64, n3: Complex) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2 = 1.0 - cos_i * cos_i;
+
+    // Products of index and cosine of the angle in each layer, kept complex
+    // for total internal reflection and absorbing substrates.
+    let n1c1 = Complex::real(n1 * cos_i);
+    let n2c2 = Complex::real(n2 * n2 - n1 * n1 * sin2).sqrt();
+    let n3c3 = (n3 * n3 - Complex::real(n1 * n1 * sin2)).sqrt();
+    let c1 = Complex::real(cos_i);
+    let c2 = n2c2 / Complex::real(n2);
+    let c3 = n3c3 / n3;
+    let (n1, n2) = (Complex::real(n1), Complex::real(n2));
+
+    let r12s = (n1c1 - n2c2) / (n1c1 + n2c2);
+    let r23s = (n2c2 - n3c3) / (n2c2 + n3c3);
+    let r12p = (n2 * c1 - n1 * c2) / (n2 * c1 + n1 * c2);
+    let r23p = (n3 * c2 - n2 * c3) / (n3 * c2 + n2 * c3);
+
+    let phase = Complex::exp_i(Complex::real(4.0 * PI * d / lambda) * n2c2);
+    let one = Complex::real(1.0);
+    let rs = (r12s + r23s * phase) / (one + r12s * r23s * phase);
+    let rp = (r12p + r23p * phase) / (one + r12p * r23p * phase);
+    (0.5 * (rs.norm_squared() + rp.norm_squared())).min(1.0)
+}
+
+// Thin transparent coating such as a soap film or an oxide layer. Light
+// reflected off its top and bottom interfaces interferes, so reflectance
+// depends on wavelength, film thickness and viewing angle.
+pub struct ThinFilm {
+    // In nm.
+    thickness: Rc<dyn Texture>,
+    ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: Rc<dyn Texture>, ior: f64) -> Self {
+        ThinFilm { thickness, ior }
+    }
+
+    // Reflectance for light arriving at cos_i through a medium of index
+    // outside, onto the film over a substrate of complex index eta + ik given
+    // per RGB channel. The result is in the same space as ray colours: RGB, or
+    // the spectral samples when wavelengths are given.
+    pub fn reflectance(
+        &self,
+        rec: &HitRecord,
+        cos_i: f64,
+        outside: f64,
+        eta: Color,
+        k: Color,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Color {
+        let d = self.thickness.scalar(rec.u, rec.v, &rec.p, rec.time).max(0.0);
+        let mut r = Color::new(0.0, 0.0, 0.0);
+        for i in 0..3 {
+            let (lambda, n3) = match wavelengths {
+                Some(w) => (
+                    w.lambda[i],
+                    Complex::new(spectrum::upsample(eta, w.lambda[i]), spectrum::upsample(k, w.lambda[i])),
+                ),
+                None => (RGB_WAVELENGTHS[i], Complex::new(eta[i], k[i])),
+            };
+            r.e[i] = airy_reflectance(cos_i, lambda, d, outside, self.ior, n3);
+        }
+        r
+    }
+}
@@ -0,0 +1,64 @@
+use crate::animation::{Quat, Track};
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// Places an object by keyframed uniform scale, rotation and translation,
+// applied in that order and evaluated at the ray's time, so that the motion
+// blurs within a frame and carries on across frames.
+pub struct AnimatedTransform {
+    object: Box<dyn Hittable>,
+    translation: Track<Vec3>,
+    rotation: Track<Quat>,
+    scale: Track<f64>,
+}
+
+impl AnimatedTransform {
+    pub fn new(object: Box<dyn Hittable>, translation: Track<Vec3>, rotation: Track<Quat>, scale: Track<f64>) -> Self {
+        AnimatedTransform {
+            object,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    fn at(&self, time: f64) -> (Vec3, Quat, f64) {
+        (self.translation.at(time), self.rotation.at(time), self.scale.at(time))
+    }
+
+    fn to_world(rec: &mut HitRecord, translation: &Vec3, rotation: &Quat, scale: f64) {
+        rec.p = *translation + scale * rotation.rotate(&rec.p);
+        rec.normal = rotation.rotate(&rec.normal);
+        rec.dpdu = scale * rotation.rotate(&rec.dpdu);
+        rec.dpdv = scale * rotation.rotate(&rec.dpdv);
+    }
+}
+
+impl Hittable for AnimatedTransform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (translation, rotation, scale) = self.at(r.time());
+        let inverse = rotation.inverse();
+        // Scaling the direction along with the origin keeps the ray parameter t.
+        let mut local = *r;
+        local.orig = inverse.rotate(&(r.origin() - translation)) / scale;
+        local.dir = inverse.rotate(&r.direction()) / scale;
+
+        let mut rec = self.object.hit(&local, t_min, t_max)?;
+        Self::to_world(&mut rec, &translation, &rotation, scale);
+        Some(rec)
+    }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let (translation, rotation, scale) = self.at(time);
+        let (mut rec, pdf) = self.object.sample_surface(time)?;
+        Self::to_world(&mut rec, &translation, &rotation, scale);
+        Some((rec, pdf / (scale * scale)))
+    }
+
+    fn surface_pdf(&self, p: &Point3, time: f64) -> f64 {
+        let (translation, rotation, scale) = self.at(time);
+        let local = rotation.inverse().rotate(&(*p - translation)) / scale;
+        self.object.surface_pdf(&local, time) / (scale * scale)
+    }
+}
@@ -0,0 +1,125 @@
+use std::rc::Rc;
+
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::rtweekend::random;
+use crate::vec3::{Point3, Vec3};
+
+// Flat triangle whose outward normal follows the winding of p0, p1, p2. Its
+// corners have texture coordinates (0, 0), (1, 0) and (0, 1) unless given.
+pub struct Triangle {
+    p: [Point3; 3],
+    uv: [(f64, f64); 3],
+    normal: Vec3,
+    area: f64,
+    mat: Rc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, mat: Rc<dyn Material>) -> Self {
+        let n = Vec3::cross(&(p1 - p0), &(p2 - p0));
+        Triangle {
+            p: [p0, p1, p2],
+            uv: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            normal: Vec3::unit_vector(&n),
+            area: 0.5 * n.length(),
+            mat,
+        }
+    }
+
+    pub fn with_uvs(mut self, uv: [(f64, f64); 3]) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    // Derivatives of the point with respect to the texture coordinates, from
+    // the edges and their changes in (u, v). Corners sharing texture
+    // coordinates get an arbitrary frame in the plane.
+    fn tangents(&self) -> (Vec3, Vec3) {
+        let (dp02, dp12) = (self.p[0] - self.p[2], self.p[1] - self.p[2]);
+        let (du02, dv02) = (self.uv[0].0 - self.uv[2].0, self.uv[0].1 - self.uv[2].1);
+        let (du12, dv12) = (self.uv[1].0 - self.uv[2].0, self.uv[1].1 - self.uv[2].1);
+        let det = du02 * dv12 - dv02 * du12;
+        if det.abs() < 1.0e-12 {
+            let frame = Onb::from_w(&self.normal);
+            return (frame.u, frame.v);
+        }
+        (
+            (dv12 * dp02 - dv02 * dp12) / det,
+            (du02 * dp12 - du12 * dp02) / det,
+        )
+    }
+
+    fn record(&self, p: Point3, b1: f64, b2: f64, t: f64, time: f64) -> HitRecord {
+        let b0 = 1.0 - b1 - b2;
+        let (dpdu, dpdv) = self.tangents();
+        HitRecord {
+            p,
+            normal: self.normal,
+            mat: self.mat.clone(),
+            t,
+            time,
+            u: b0 * self.uv[0].0 + b1 * self.uv[1].0 + b2 * self.uv[2].0,
+            v: b0 * self.uv[0].1 + b1 * self.uv[1].1 + b2 * self.uv[2].1,
+            dpdu,
+            dpdv,
+            front_face: true,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    // Moller-Trumbore: solves for t and the barycentric coordinates of p1 and p2.
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let e1 = self.p[1] - self.p[0];
+        let e2 = self.p[2] - self.p[0];
+        let pvec = Vec3::cross(&r.direction(), &e2);
+        let det = Vec3::dot(&e1, &pvec);
+        if det.abs() < 1.0e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.origin() - self.p[0];
+        let b1 = Vec3::dot(&tvec, &pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+        let qvec = Vec3::cross(&tvec, &e1);
+        let b2 = Vec3::dot(&r.direction(), &qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+        let t = Vec3::dot(&e2, &qvec) * inv_det;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let mut rec = self.record(r.at(t), b1, b2, t, r.time());
+        rec.set_face_normal(r, &self.normal);
+        Some(rec)
+    }
+
+    fn sample_surface(&self, time: f64) -> Option<(HitRecord, f64)> {
+        let su = random().sqrt();
+        let (b1, b2) = (1.0 - su, random() * su);
+        let p = (1.0 - b1 - b2) * self.p[0] + b1 * self.p[1] + b2 * self.p[2];
+        Some((self.record(p, b1, b2, 0.0, time), 1.0 / self.area))
+    }
+
+    fn surface_pdf(&self, p: &Point3, _time: f64) -> f64 {
+        let d = *p - self.p[0];
+        if Vec3::dot(&d, &self.normal).abs() > 1.0e-6 * self.area.sqrt() {
+            return 0.0;
+        }
+        // Barycentric coordinates from the areas of the sub-triangles.
+        let b1 = Vec3::dot(&Vec3::cross(&d, &(self.p[2] - self.p[0])), &self.normal) / (2.0 * self.area);
+        let b2 = Vec3::dot(&Vec3::cross(&(self.p[1] - self.p[0]), &d), &self.normal) / (2.0 * self.area);
+        if b1 < -1.0e-9 || b2 < -1.0e-9 || b1 + b2 > 1.0 + 1.0e-9 {
+            return 0.0;
+        }
+        1.0 / self.area
+    }
+}
@@ -47,7 +47,7 @@ impl Vec3 {
 
     pub fn random_in_hemisphere(normal: &Self) -> Self {
         let in_unit_sphere = Self::random_in_unit_sphere();
-        if Self::dot(&in_unit_sphere, &normal) > 0.0 {
+        if Self::dot(&in_unit_sphere, normal) > 0.0 {
             in_unit_sphere
         } else {
             -in_unit_sphere
@@ -100,7 +100,7 @@ impl Vec3 {
     }
 
     pub fn unit_vector(v: &Self) -> Self {
-        v.clone() / v.length()
+        *v / v.length()
     }
 }
 
@@ -214,7 +214,16 @@ pub type Point3 = Vec3;
 pub type Color = Vec3;
 
 impl Color {
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x() + 0.7152 * self.y() + 0.0722 * self.z()
+    }
+
     pub fn write_color(pixel_color: Color, samples_per_pixel: i32) {
+        let [r, g, b] = Color::to_rgb8(pixel_color, samples_per_pixel);
+        println!("{} {} {}", r, g, b);
+    }
+
+    pub fn to_rgb8(pixel_color: Color, samples_per_pixel: i32) -> [u8; 3] {
         let r = pixel_color.x();
         let g = pixel_color.y();
         let b = pixel_color.z();
@@ -225,12 +234,11 @@ impl Color {
         let g = (g * scale).sqrt();
         let b = (b * scale).sqrt();
 
-        // Write the translated [0, 255] value of each color component.
-        println!(
-            "{} {} {}",
-            (256.0 * clamp(r, 0.0, 0.999)) as i32,
-            (256.0 * clamp(g, 0.0, 0.999)) as i32,
-            (256.0 * clamp(b, 0.0, 0.999)) as i32
-        );
+        // The translated [0, 255] value of each color component.
+        [
+            (256.0 * clamp(r, 0.0, 0.999)) as u8,
+            (256.0 * clamp(g, 0.0, 0.999)) as u8,
+            (256.0 * clamp(b, 0.0, 0.999)) as u8,
+        ]
     }
 }